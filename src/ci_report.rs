@@ -0,0 +1,231 @@
+use std::{collections::HashMap, io::{self, Write}};
+
+use crate::{output::{Renderer, Run}, report::{Report, CORRUPTED, MODIFIED}, root::Root, scan_log::ScanLog};
+
+/// Result of one tracked file for CI systems.
+#[derive(Debug, PartialEq, Eq)]
+struct Case<'a> {
+    path: &'a str,
+    outcome: Outcome,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    Passed,
+    /// Kind of the change and its description.
+    Failed(&'static str, String),
+    /// The file could not be read.
+    Error(String),
+}
+
+/// Every file in the current scan and every removed file or directory is a case. Content changes, removals of
+/// files and directories, moves, renames by normalization and unreadable files fail. Added files, added
+/// directories and changes of metadata only pass. A rename whose content also changed fails as the content change.
+fn cases<'a>(report: &Report<'a>, log: &'a ScanLog) -> Vec<Case<'a>> {
+    let mut failures: HashMap<&str, Outcome> = HashMap::new();
+    for c in report.changes().into_iter() {
+        let outcome = match c.kind {
+            MODIFIED | CORRUPTED => {
+                let what = if c.kind == CORRUPTED {
+                    "Suspected corruption: content changed without changing size or modification time"
+                } else {
+                    "Content changed"
+                };
+                Outcome::Failed(c.kind, format!(
+                    "{}: {} -> {}", what, c.old_hash.unwrap_or_default(), c.new_hash.unwrap_or_default()
+                ))
+            }
+            "removed" | "removed_dir" => Outcome::Failed(c.kind, "Removed".to_owned()),
+            "moved" | "normalization_match" if c.old_hash == c.new_hash => Outcome::Failed(c.kind, format!("Moved from {}", c.old_path)),
+            // Additions and changes of metadata only pass.
+            _ => continue,
+        };
+        failures.insert(c.path, outcome);
+    }
+    for e in log.errors.iter() {
+        failures.insert(&e.path, Outcome::Error(e.message.clone()));
+    }
+
+    let mut ret: Vec<Case<'a>> = report.to.entries.iter().filter(|e| ! e.is_dir())
+        .map(|e| Case { path: &e.file_path, outcome: failures.remove(e.file_path.as_str()).unwrap_or(Outcome::Passed) })
+        .collect();
+    ret.extend(failures.into_iter().map(|(path, outcome)| Case { path, outcome }));
+    ret.sort_by(|a, b| a.path.cmp(b.path));
+    ret
+}
+
+/// Writes a JUnit XML document with one test suite for each root.
+pub fn write_junit<W: Write>(w: &mut W, report: &Report, log: &ScanLog, roots: &[Root]) -> io::Result<()> {
+    let cases = cases(report, log);
+    let count = |cases: &[&Case], f: fn(&Outcome) -> bool| cases.iter().filter(|c| f(&c.outcome)).count();
+    let failed = |o: &Outcome| matches!(o, Outcome::Failed(..));
+    let error = |o: &Outcome| matches!(o, Outcome::Error(_));
+
+    let all: Vec<&Case> = cases.iter().collect();
+    writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        w, r#"<testsuites name="disk_scrub" tests="{}" failures="{}" errors="{}">"#,
+        all.len(), count(&all, failed), count(&all, error)
+    )?;
+    for r in roots.iter() {
+        let prefix = r.prefix();
+        let suite: Vec<&Case> = cases.iter().filter(|c| c.path.starts_with(&prefix)).collect();
+        let name = if r.name.is_empty() { r.path.to_string_lossy().to_string() } else { r.name.clone() };
+        writeln!(
+            w, r#"  <testsuite name="{}" tests="{}" failures="{}" errors="{}">"#,
+            xml_escape(&name), suite.len(), count(&suite, failed), count(&suite, error)
+        )?;
+        for c in suite.iter() {
+            let name = xml_escape(c.path);
+            match &c.outcome {
+                Outcome::Passed => writeln!(w, r#"    <testcase classname="disk_scrub" name="{}"/>"#, name)?,
+                Outcome::Failed(kind, message) => {
+                    writeln!(w, r#"    <testcase classname="disk_scrub" name="{}">"#, name)?;
+                    writeln!(w, r#"      <failure type="{}" message="{}"/>"#, kind, xml_escape(message))?;
+                    writeln!(w, "    </testcase>")?;
+                }
+                Outcome::Error(message) => {
+                    writeln!(w, r#"    <testcase classname="disk_scrub" name="{}">"#, name)?;
+                    writeln!(w, r#"      <error type="error" message="{}"/>"#, xml_escape(message))?;
+                    writeln!(w, "    </testcase>")?;
+                }
+            }
+        }
+        writeln!(w, "  </testsuite>")?;
+    }
+    writeln!(w, "</testsuites>")
+}
+
+/// Writes a TAP version 13 stream with one test point for each file.
+pub fn write_tap<W: Write>(w: &mut W, report: &Report, log: &ScanLog) -> io::Result<()> {
+    let cases = cases(report, log);
+    writeln!(w, "TAP version 13")?;
+    writeln!(w, "1..{}", cases.len())?;
+    for (i, c) in cases.iter().enumerate() {
+        let (ok, kind, message) = match &c.outcome {
+            Outcome::Passed => ("ok", None, None),
+            Outcome::Failed(kind, message) => ("not ok", Some(*kind), Some(message)),
+            Outcome::Error(message) => ("not ok", Some("error"), Some(message)),
+        };
+        writeln!(w, "{} {} - {}", ok, i + 1, tap_escape(c.path))?;
+        if let (Some(kind), Some(message)) = (kind, message) {
+            writeln!(w, "  ---")?;
+            writeln!(w, "  kind: {}", kind)?;
+            writeln!(w, "  message: {}", serde_json::to_string(message)?)?;
+            writeln!(w, "  ...")?;
+        }
+    }
+    Ok(())
+}
+
+pub struct JunitRenderer<W: Write>(pub W);
+
+impl<W: Write> Renderer for JunitRenderer<W> {
+    fn render(&mut self, report: &Report, log: &ScanLog, run: &Run) -> io::Result<()> {
+        write_junit(&mut self.0, report, log, &run.roots)?;
+        self.0.flush()
+    }
+}
+
+pub struct TapRenderer<W: Write>(pub W);
+
+impl<W: Write> Renderer for TapRenderer<W> {
+    fn render(&mut self, report: &Report, log: &ScanLog, _run: &Run) -> io::Result<()> {
+        write_tap(&mut self.0, report, log)?;
+        self.0.flush()
+    }
+}
+
+/// Escapes markup characters. Control characters that XML 1.0 cannot represent are written as '\u{..}'.
+fn xml_escape(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => ret.push_str("&amp;"),
+            '<' => ret.push_str("&lt;"),
+            '>' => ret.push_str("&gt;"),
+            '"' => ret.push_str("&quot;"),
+            '\'' => ret.push_str("&apos;"),
+            '\t' | '\n' | '\r' => ret.push_str(&format!("&#{};", c as u32)),
+            c if c < ' ' || c == '\u{fffe}' || c == '\u{ffff}' => ret.push_str(&c.escape_unicode().to_string()),
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+/// Escapes characters that have meanings in a TAP description: '#' starts a directive and a line break ends the line.
+fn tap_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('#', "\\#").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control_file::{ControlFile, Header, entry};
+    use crate::{report::Report, root::Root, scan_log::{ScanLog, ScanError}};
+    use super::{write_junit, write_tap, xml_escape, tap_escape};
+
+    #[test]
+    fn can_write_junit_and_tap() {
+        let from = ControlFile {
+            header: Header::default(), entries: vec![entry("a", "a", None), entry("b", "b", None), entry("c", "c", None), entry("d/", "", None)],
+        };
+        let to = ControlFile {
+            header: Header::default(), entries: vec![entry("a", "a", None), entry("c", "c0", None), entry("d/", "", None), entry("e<&>", "e", None)],
+        };
+        let report = Report::new(&from, &to);
+        let log = ScanLog {
+            errors: vec![ScanError { path: "f".to_owned(), message: "denied".to_owned() }],
+            ..ScanLog::default()
+        };
+
+        let mut buf = vec![];
+        write_junit(&mut buf, &report, &log, &[Root::new("", "/tmp")]).unwrap();
+        let xml = String::from_utf8(buf).unwrap();
+        assert!(xml.contains(r#"<testsuites name="disk_scrub" tests="5" failures="2" errors="1">"#));
+        assert!(xml.contains(r#"<testcase classname="disk_scrub" name="a"/>"#));
+        assert!(xml.contains(r#"<failure type="removed" message="Removed"/>"#));
+        assert!(xml.contains(r#"<failure type="modified" message="Content changed: "#));
+        assert!(xml.contains(r#"<error type="error" message="denied"/>"#));
+        assert!(xml.contains(r#"name="e&lt;&amp;&gt;"/>"#));
+
+        let mut buf = vec![];
+        write_tap(&mut buf, &report, &log).unwrap();
+        let tap = String::from_utf8(buf).unwrap();
+        let lines: Vec<&str> = tap.lines().collect();
+        assert_eq!(lines[0], "TAP version 13");
+        assert_eq!(lines[1], "1..5");
+        assert_eq!(lines[2], "ok 1 - a");
+        assert_eq!(lines[3], "not ok 2 - b");
+        assert_eq!(lines[5], "  kind: removed");
+        assert!(tap.contains("not ok 5 - f\n  ---\n  kind: error\n  message: \"denied\"\n"));
+    }
+
+    #[test]
+    fn removed_dirs_and_moves_fail() {
+        let from = ControlFile {
+            header: Header::default(),
+            entries: vec![entry("caf\u{e9}", "x", None), entry("d/", "", None), entry("na\u{ef}ve", "y", None), entry("old", "z", None)],
+        };
+        let to = ControlFile {
+            header: Header::default(),
+            entries: vec![entry("cafe\u{301}", "x", None), entry("nai\u{308}ve", "y0", None), entry("new", "z", None)],
+        };
+        let report = Report::new(&from, &to);
+
+        let mut buf = vec![];
+        write_tap(&mut buf, &report, &ScanLog::default()).unwrap();
+        let tap = String::from_utf8(buf).unwrap();
+        assert_eq!(tap.lines().nth(1), Some("1..4"));
+        assert!(tap.contains("not ok 1 - cafe\u{301}\n  ---\n  kind: normalization_match\n  message: \"Moved from caf\u{e9}\"\n"));
+        assert!(tap.contains("not ok 2 - d/\n  ---\n  kind: removed_dir\n  message: \"Removed\"\n"));
+        assert!(tap.contains("not ok 3 - nai\u{308}ve\n  ---\n  kind: modified\n"));
+        assert!(tap.contains("not ok 4 - new\n  ---\n  kind: moved\n  message: \"Moved from old\"\n"));
+    }
+
+    #[test]
+    fn can_escape() {
+        assert_eq!(xml_escape("a\"b'\u{1}\n"), "a&quot;b&apos;\\u{1}&#10;");
+        assert_eq!(tap_escape("a#b\nc\\"), "a\\#b\\nc\\\\");
+    }
+}
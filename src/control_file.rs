@@ -1,56 +1,188 @@
-use std::{io::{Error, self, BufRead, ErrorKind, BufWriter, Read}, path::Path, fs::File, fmt::{Display, self}, ops::Index};
+use std::{io::{Error, self, BufRead, BufWriter, Read}, path::Path, fs::File, fmt::{Display, self}, ops::Index, time::Instant};
 use std::io::Write;
 
 use sha2::{Sha256, Digest};
 
-use crate::{tree, io_error::IoError, Cli};
+use crate::{tree::{self, WalkEntry}, exclude::Include, marker::SkipReason, io_error::IoError, metadata::{Metadata, Stat}, root::Root, scan_log::{ScanLog, ScanError, Excluded, RootScan}, event::Event, Cli};
+
+/// Lines starting with this marker hold settings of the run that produced the Controlfile.
+const HEADER_MARKER: &str = "#!";
+/// First line of the header block. Headers are only recognized in the block at the top of the file so that
+/// entries whose path starts with the marker are read back as entries.
+const HEADER_START: &str = "#!disk_scrub\t1";
+/// Last line of the header block.
+const HEADER_END: &str = "#!end";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Header {
+    pub roots: Vec<Root>,
+    /// Include patterns the Controlfile was made with. Used when none is given in the command line.
+    pub include: Vec<String>,
+    /// Whether the Controlfile was made with '-m'. Used unless '--no-track-metadata' is given.
+    pub track_metadata: bool,
+}
+
+impl Header {
+    fn parse_line(&mut self, inp: &str) -> Result<(), ParseError> {
+        let cols: Vec<&str> = inp.split('\t').collect();
+        match cols[0] {
+            "root" if cols.len() == 3 => self.roots.push(Root::new(cols[1], cols[2])),
+            "include" if cols.len() == 2 => self.include.push(cols[1].to_owned()),
+            "track_metadata" if cols.len() == 1 => self.track_metadata = true,
+            _ => return Err(ParseError::InvalidHeader(inp.to_owned())),
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Header {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", HEADER_START)?;
+        for r in self.roots.iter() {
+            writeln!(f, "{}root\t{}\t{}", HEADER_MARKER, r.name, r.path.to_string_lossy())?;
+        }
+        for i in self.include.iter() {
+            writeln!(f, "{}include\t{}", HEADER_MARKER, i)?;
+        }
+        if self.track_metadata {
+            writeln!(f, "{}track_metadata", HEADER_MARKER)?;
+        }
+        writeln!(f, "{}", HEADER_END)
+    }
+}
 
 pub struct ControlFile {
+    pub header: Header,
     pub entries: Vec<ControlFileEntry>,    
 }
 
 impl ControlFile {
     pub fn empty() -> Self {
-        Self { entries: vec![] }
+        Self { header: Header::default(), entries: vec![] }
     }
 
     pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let mut header = Header::default();
         let mut recs: Vec<ControlFileEntry> = vec![];
         let lines = io::BufReader::new(File::open(&path)?).lines();
-        let mut line_no: usize = 1;
-        for l in lines {
+        // Controlfiles of older versions have no header block.
+        let mut in_header = false;
+        for (idx, l) in lines.enumerate() {
             let l = l?;
-            match ControlFileEntry::parse(&l) {
-                Err(parse_error) =>
-                    return Err(Error::new(ErrorKind::Other, format!("{:?}({}): {} '{}'.", path.as_ref().to_str(), line_no, parse_error, l))),
-                Ok(e) => recs.push(e),
+            let parsed = if idx == 0 && l == HEADER_START {
+                in_header = true;
+                Ok(())
+            } else if in_header && l == HEADER_END {
+                in_header = false;
+                Ok(())
+            } else if in_header {
+                match l.strip_prefix(HEADER_MARKER) {
+                    Some(h) => header.parse_line(h),
+                    None => Err(ParseError::InvalidHeader(l.clone())),
+                }
+            } else {
+                ControlFileEntry::parse(&l).map(|e| recs.push(e))
+            };
+            if let Err(parse_error) = parsed {
+                return Err(Error::other(format!("{:?}({}): {} '{}'.", path.as_ref().to_str(), idx + 1, parse_error, l)));
             }
-            line_no += 1;
         }
 
-        Ok(Self { entries: recs })
+        Ok(Self { header, entries: recs })
     }
 
     pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
         let mut writer = BufWriter::new(File::create(&path)?);
+        write!(writer, "{}", self.header)?;
         for e in self.entries.iter() {
-            write!(writer, "{}\n", e)?;
+            writeln!(writer, "{}", e)?;
         }
         Ok(())
     }
 
-    pub fn load_from_dir<P: AsRef<Path>>(dir: P, cli: &Cli) -> Result<Self, IoError> {
-        let mut list = tree::list_recursive(&dir, cli)?;
-        list.sort();
-        let mut recs = Vec::with_capacity(list.len());
-        for f in list.iter() {
-            recs.push(
-                ControlFileEntry::from_file(&dir, f.clone()).map_err(
-                    |err| IoError { cause: err, message: "Cannot read file.".to_owned(), path: Some(dir.as_ref().join(f).to_owned()) }
-                )?
-            );
+    /// Scrubs all roots. Entries of each root are prefixed by its name.
+    pub fn load_from_roots(roots: &[Root], cli: &Cli, log: &mut ScanLog, on_event: &mut dyn FnMut(&Event)) -> Result<Self, IoError> {
+        let mut recs: Vec<ControlFileEntry> = vec![];
+        for r in roots.iter() {
+            let started_at = Instant::now();
+            let mut entries = Self::load_from_dir(&r.path, &r.prefix(), cli, log, on_event)?.entries;
+            let files = entries.iter().filter_map(|e| e.stat.as_ref());
+            log.roots.push(RootScan {
+                name: r.name.clone(), files: files.clone().count(), bytes: files.map(|s| s.size).sum(),
+                seconds: started_at.elapsed().as_secs_f64(),
+            });
+            recs.append(&mut entries);
+        }
+        recs.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+
+        let header = Header { roots: roots.to_vec(), include: cli.include.clone(), track_metadata: cli.track_metadata };
+        Ok(Self { header, entries: recs })
+    }
+
+    /// Scrubs the directory. Paths of entries, log records and events are prefixed by the prefix.
+    /// Files that cannot be read are recorded in the log and left out instead of aborting the scan.
+    pub fn load_from_dir<P: AsRef<Path>>(
+        dir: P, prefix: &str, cli: &Cli, log: &mut ScanLog, on_event: &mut dyn FnMut(&Event)
+    ) -> Result<Self, IoError> {
+        let mut recs: Vec<ControlFileEntry> = vec![];
+        // Files are hashed while the rest of the tree is still being walked.
+        tree::walk(&dir, cli, |e| {
+            let f = match e {
+                WalkEntry::Found(f) => f,
+                WalkEntry::Excluded(path, rule) => {
+                    if cli.show_excluded {
+                        log.excluded.push(Excluded { path: format!("{}{}", prefix, path), rule });
+                    }
+                    return Ok(());
+                }
+                WalkEntry::Skipped(path, reason) => {
+                    let path = format!("{}{}", prefix, path);
+                    log.count_skipped(&reason);
+                    if reason == SkipReason::StateFile {
+                        log.state_files.push(path.clone());
+                    }
+                    if cli.show_excluded {
+                        log.excluded.push(Excluded { path, rule: reason.to_string() });
+                    }
+                    return Ok(());
+                }
+            };
+            match ControlFileEntry::from_file(&dir, f.clone(), cli.track_metadata) {
+                Ok(mut e) => {
+                    e.file_path.insert_str(0, prefix);
+                    if ! e.is_dir() {
+                        on_event(&Event::FileHashed { path: &e.file_path, hash: hex::encode(&e.sha256), entry: &e });
+                    }
+                    recs.push(e);
+                }
+                Err(err) => {
+                    let err = ScanError { path: format!("{}{}", prefix, f), message: err.to_string() };
+                    on_event(&Event::Error { path: &err.path, message: &err.message });
+                    log.errors.push(err);
+                }
+            }
+            Ok(())
+        })?;
+        recs.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+        Ok(Self { header: Header::default(), entries: recs, })
+    }
+
+    /// Puts back previous entries of files that could not be read so that they are not reported as removed.
+    pub fn keep_unreadable(&mut self, prev: &ControlFile, errors: &[ScanError]) {
+        for err in errors.iter() {
+            if let Some(e) = prev.get(&err.path) {
+                self.entries.push(e.clone());
+            }
         }
-        Ok(Self { entries: recs, })
+        self.entries.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+    }
+
+    /// Drops entries that do not match the include patterns so that narrowing the patterns does not report them as removed.
+    pub fn retain_included(&mut self, roots: &[Root], include: &Include) {
+        self.entries.retain(|e| {
+            let rel = roots.iter().find_map(|r| e.file_path.strip_prefix(&r.prefix())).unwrap_or(&e.file_path);
+            include.includes(rel, e.is_dir())
+        });
     }
 
     #[allow(dead_code)]
@@ -58,6 +190,7 @@ impl ControlFile {
         self.entries.len()
     }
 
+    #[allow(dead_code)]
     pub fn files(&self) -> Vec<&str> {
         let mut ret = Vec::with_capacity(self.entries.len());
         for e in self.entries.iter() {
@@ -84,54 +217,90 @@ impl Index<usize> for ControlFile {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
 pub enum ParseError {
     InvalidColumnCount(usize),
     InvalidHashFormat(String),
+    InvalidMetadataFormat(String),
+    InvalidStatFormat(String),
+    InvalidHeader(String),
 }
 
 impl Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            &ParseError::InvalidColumnCount(count) => write!(f, "Invalid column count(={}) expected 2.", count),
+            &ParseError::InvalidColumnCount(count) => write!(f, "Invalid column count(={}) expected 2, 4, 6 or 8.", count),
             ParseError::InvalidHashFormat(s) => write!(f, "Invalid hash format '{:?}'.", s),
+            ParseError::InvalidMetadataFormat(s) => write!(f, "Invalid metadata format '{:?}'.", s),
+            ParseError::InvalidStatFormat(s) => write!(f, "Invalid size or modification time '{:?}'.", s),
+            ParseError::InvalidHeader(s) => write!(f, "Invalid header '{:?}'.", s),
         }
     }
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub struct ControlFileEntry {
+    /// Relative path. Directories end with '/'.
     pub file_path: String,
+    /// Empty for directories.
     pub sha256: Vec<u8>,
+    /// Present only for files. Absent in Controlfiles of older versions.
+    pub stat: Option<Stat>,
+    /// Present only when the entry was captured with metadata tracking.
+    pub metadata: Option<Metadata>,
 }
 
 impl ControlFileEntry {
-    pub fn from_file<P: AsRef<Path>>(root: P, file_path: String) -> Result<Self, Error> {
+    pub fn from_file<P: AsRef<Path>>(root: P, file_path: String, track_metadata: bool) -> Result<Self, Error> {
         let path = root.as_ref().join(&file_path);
+        let metadata = if track_metadata { Some(Metadata::from_path(&path)?) } else { None };
+        let (sha256, stat) = if file_path.ends_with('/') {
+            (vec![], None)
+        } else {
+            (file_hash(&path, None)?, Some(Stat::from_path(&path)?))
+        };
 
         Ok(
             Self {
-                file_path, sha256: file_hash(&path, None)?
+                file_path, sha256, stat, metadata,
             }
         )
     }
 
+    pub fn is_dir(&self) -> bool {
+        self.file_path.ends_with('/')
+    }
+
     pub fn parse(inp: &str) -> Result<Self, ParseError> {
-        let cols: Vec<&str> = inp.split("\t").collect();
-        if cols.len() != 2 {
+        let cols: Vec<&str> = inp.split('\t').collect();
+        if ! [2, 4, 6, 8].contains(&cols.len()) {
             return Err(ParseError::InvalidColumnCount(cols.len()));
         }
         let file_path = cols[0].to_owned();
-        let sha256 = match hex::decode(cols[1]) {
-            Err(_) => { return Err(ParseError::InvalidHashFormat(cols[1].to_owned())); },
-            Ok(hash) => hash,
+        let sha256 = if file_path.ends_with('/') && cols[1] == DIR_HASH {
+            vec![]
+        } else {
+            match hex::decode(cols[1]) {
+                Ok(hash) if hash.len() == 32 && !file_path.ends_with('/') => hash,
+                _ => { return Err(ParseError::InvalidHashFormat(cols[1].to_owned())); },
+            }
+        };
+        // Columns of size and modification time come first if present.
+        let stat = if cols.len() == 4 || cols.len() == 8 {
+            Some(Stat::parse(cols[2], cols[3])?)
+        } else {
+            None
+        };
+        let meta_cols = &cols[if stat.is_some() { 4 } else { 2 }..];
+        let metadata = if meta_cols.len() == 4 {
+            Some(Metadata::parse(meta_cols[0], meta_cols[1], meta_cols[2], meta_cols[3])?)
+        } else {
+            None
         };
-        if sha256.len() != 32 {
-            return Err(ParseError::InvalidHashFormat(cols[1].to_owned()));
-        }
 
         Ok(
             ControlFileEntry {
-                file_path: file_path.to_owned(), sha256,
+                file_path, sha256, stat, metadata,
             }
         )
     }
@@ -139,10 +308,24 @@ impl ControlFileEntry {
 
 impl fmt::Display for ControlFileEntry {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}\t{}", self.file_path, hex::encode(&self.sha256))
+        if self.is_dir() {
+            write!(f, "{}\t{}", self.file_path, DIR_HASH)?;
+        } else {
+            write!(f, "{}\t{}", self.file_path, hex::encode(&self.sha256))?;
+        }
+        if let Some(stat) = &self.stat {
+            write!(f, "\t{}", stat)?;
+        }
+        if let Some(metadata) = &self.metadata {
+            write!(f, "\t{}", metadata)?;
+        }
+        Ok(())
     }
 }
 
+/// Placeholder written in the hash column of directory entries.
+const DIR_HASH: &str = "-";
+
 const READ_BUF_SIZE: usize = 16 * 1024;
 
 fn file_hash(path: &Path, buf_size: Option<usize>) -> Result<Vec<u8>, Error> {
@@ -167,6 +350,17 @@ pub fn str_hash(s: &str) -> Vec<u8> {
     hasher.finalize().to_vec()
 }
 
+/// Entry of a file that has the content. It has a stat of the content length if mtime_sec is specified.
+#[cfg(test)]
+pub(crate) fn entry(path: &str, content: &str, mtime_sec: Option<i64>) -> ControlFileEntry {
+    ControlFileEntry {
+        file_path: path.to_owned(),
+        sha256: str_hash(content),
+        stat: mtime_sec.map(|mtime_sec| Stat { size: content.len() as u64, mtime_sec, mtime_nsec: 0 }),
+        metadata: None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
@@ -174,8 +368,11 @@ mod tests {
     use std::{fs::File, os::unix::prelude::FileExt};
     use tempfile::tempdir;
     use crate::Cli;
-    use crate::control_file::{ParseError, file_hash};
-    use super::{ControlFileEntry, ControlFile};
+    use crate::control_file::{ParseError, entry, file_hash};
+    use crate::root::Root;
+    use crate::metadata::Stat;
+    use crate::scan_log::{ScanLog, ScanError, Excluded};
+    use super::{ControlFileEntry, ControlFile, Header};
     use super::str_hash;
 
     #[test]
@@ -229,22 +426,53 @@ mod tests {
             ControlFileEntry {
                 file_path: "ABC".to_owned(),
                 sha256: str_hash("ABC"),
+                stat: None,
+                metadata: None,
             },
             ControlFileEntry {
                 file_path: "DEF".to_owned(),
                 sha256: str_hash("DEF"),
+                stat: None,
+                metadata: None,
             },
         ];
 
         let tmp_dir = tempdir().unwrap();
         let path = tmp_dir.path().join("foo0.ctrl");
-        let cf = ControlFile { entries };
+        let cf = ControlFile {
+            header: Header { roots: vec![Root::new("foo", "/foo")], include: vec!["*.raw".to_owned()], track_metadata: true },
+            entries
+        };
         cf.save_to_file(&path).unwrap();
 
         let loaded = ControlFile::load_from_file(&path).unwrap();
+        assert_eq!(loaded.header, cf.header);
         assert_eq!(loaded.entries, cf.entries);
     }
 
+    #[test]
+    fn can_save_load_path_starting_with_header_marker() {
+        let tmp_dir = tempdir().unwrap();
+        fs::write(tmp_dir.path().join("#!x"), b"012").unwrap();
+        fs::write(tmp_dir.path().join("#!end"), b"ABC").unwrap();
+        let roots = vec![Root::new("", tmp_dir.path())];
+        let cf = ControlFile::load_from_roots(&roots, &Cli::default(), &mut ScanLog::default(), &mut |_| {}).unwrap();
+        assert_eq!(cf.files(), vec!["#!end", "#!x"]);
+
+        let ctrl_dir = tempdir().unwrap();
+        let path = ctrl_dir.path().join("Controlfile");
+        cf.save_to_file(&path).unwrap();
+        let loaded = ControlFile::load_from_file(&path).unwrap();
+        assert_eq!(loaded.header, cf.header);
+        assert_eq!(loaded.entries, cf.entries);
+
+        // Controlfiles of older versions have no header block.
+        fs::write(&path, format!("#!x\t{}\n", hex::encode(str_hash("012")))).unwrap();
+        let loaded = ControlFile::load_from_file(&path).unwrap();
+        assert_eq!(loaded.header, Header::default());
+        assert_eq!(loaded.files(), vec!["#!x"]);
+    }
+
     #[test]
     fn simple_hash_calc() {
         // $ echo -n 012 | sha256sum
@@ -266,9 +494,49 @@ mod tests {
         File::create(&path).unwrap()
             .write_all_at(&[0x30u8, 0x31u8, 0x32u8], 0).unwrap();
 
-        let cfe = ControlFileEntry::from_file(&tmp_dir, "foo.txt".to_owned()).unwrap();
+        let cfe = ControlFileEntry::from_file(&tmp_dir, "foo.txt".to_owned(), false).unwrap();
         assert_eq!(cfe.file_path, "foo.txt");
-        assert_eq!(cfe.sha256, str_hash("012"))
+        assert_eq!(cfe.sha256, str_hash("012"));
+        assert_eq!(cfe.stat.as_ref().unwrap().size, 3);
+        assert_eq!(cfe.metadata, None);
+    }
+
+    #[test]
+    fn can_parse_dir() {
+        let e = ControlFileEntry::parse("foo/\t-").unwrap();
+        assert!(e.is_dir());
+        assert!(e.sha256.is_empty());
+        assert_eq!(e.to_string(), "foo/\t-");
+
+        assert_eq!(ControlFileEntry::parse("foo\t-").err().unwrap(), ParseError::InvalidHashFormat("-".to_owned()));
+    }
+
+    #[test]
+    fn can_parse_stat() {
+        let hash = "00112233445566778899aabbccddeeff0112233445566778899aabbccddeeff0";
+        let inp = format!("ABC\t{}\t3\t1700000000.000000001", hash);
+        let e = ControlFileEntry::parse(&inp).unwrap();
+        assert_eq!(e.stat, Some(Stat { size: 3, mtime_sec: 1700000000, mtime_nsec: 1 }));
+        assert_eq!(e.metadata, None);
+        assert_eq!(e.to_string(), inp);
+
+        let inp = format!("ABC\t{}\t3\t1700000000.000000001\t644\t1000\t100\t{}", hash, hash);
+        let e = ControlFileEntry::parse(&inp).unwrap();
+        assert_eq!(e.stat.as_ref().unwrap().size, 3);
+        assert_eq!(e.metadata.as_ref().unwrap().uid, 1000);
+        assert_eq!(e.to_string(), inp);
+    }
+
+    #[test]
+    fn can_parse_metadata() {
+        let hash = "00112233445566778899aabbccddeeff0112233445566778899aabbccddeeff0";
+        let inp = format!("ABC\t{}\t644\t1000\t100\t{}", hash, hash);
+        let e = ControlFileEntry::parse(&inp).unwrap();
+        let meta = e.metadata.as_ref().unwrap();
+        assert_eq!(meta.mode, 0o644);
+        assert_eq!(meta.uid, 1000);
+        assert_eq!(meta.gid, 100);
+        assert_eq!(e.to_string(), inp);
     }
 
     #[test]
@@ -284,33 +552,75 @@ mod tests {
             foo1.write_all(b"ABC").unwrap();
         }
 
-        let cli = Cli {
-          control_file : "".to_owned(),
-          exclude: vec![],
-          target_dir: "".to_owned(),
-        };
-        let list = ControlFile::load_from_dir(&tmp_dir, &cli).unwrap();
-        assert_eq!(list.len(), 2);
+        let cli = Cli::default();
+        let list = ControlFile::load_from_dir(&tmp_dir, "", &cli, &mut ScanLog::default(), &mut |_| {}).unwrap();
+        assert_eq!(list.len(), 3);
         let e = &list.entries[0];
+        assert_eq!(e.file_path, "foo/");
+        assert!(e.is_dir());
+        assert!(e.sha256.is_empty());
+
+        let e = &list.entries[1];
         assert_eq!(e.file_path, "foo/foo1.txt");
         assert_eq!(e.sha256, str_hash("ABC"));
 
-        let e = &list.entries[1];
+        let e = &list.entries[2];
         assert_eq!(e.file_path, "foo0.txt");
         assert_eq!(e.sha256, str_hash("012"));
     }
 
+    #[test]
+    fn read_from_roots() {
+        let tmp_dir0 = tempdir().unwrap();
+        File::create(tmp_dir0.path().join("foo.txt")).unwrap().write_all(b"012").unwrap();
+        let tmp_dir1 = tempdir().unwrap();
+        File::create(tmp_dir1.path().join("foo.txt")).unwrap().write_all(b"ABC").unwrap();
+
+        let roots = vec![Root::new("b", tmp_dir0.path()), Root::new("a", tmp_dir1.path())];
+        let cli = Cli {
+            exclude: vec!["*.tmp".to_owned()],
+            show_excluded: true,
+            ..Cli::default()
+        };
+        File::create(tmp_dir0.path().join("foo.tmp")).unwrap();
+        let mut log = ScanLog::default();
+        let list = ControlFile::load_from_roots(&roots, &cli, &mut log, &mut |_| {}).unwrap();
+        assert_eq!(list.files(), vec!["a/foo.txt", "b/foo.txt"]);
+        assert_eq!(log.excluded, vec![Excluded { path: "b/foo.tmp".to_owned(), rule: "-X '*.tmp'".to_owned() }]);
+        assert_eq!(list.get("b/foo.txt").unwrap().sha256, str_hash("012"));
+        assert_eq!(list.header.roots, roots);
+        assert_eq!(log.roots.iter().map(|r| (r.name.as_str(), r.files, r.bytes)).collect::<Vec<_>>(), vec![("b", 1, 3), ("a", 1, 3)]);
+    }
+
+    #[test]
+    fn can_keep_unreadable() {
+        let prev = ControlFile { header: Header::default(), entries: vec![entry("a", "a", None), entry("b", "b", None), entry("c", "c", None)] };
+        let mut cur = ControlFile { header: Header::default(), entries: vec![entry("c", "c", None)] };
+        let errors = vec![
+            ScanError { path: "a".to_owned(), message: "denied".to_owned() },
+            ScanError { path: "d".to_owned(), message: "denied".to_owned() },
+        ];
+
+        cur.keep_unreadable(&prev, &errors);
+        assert_eq!(cur.files(), vec!["a", "c"]);
+    }
+
     #[test]
     fn can_retrieve_file_list() {
         let cf = ControlFile {
+            header: Header::default(),
             entries: vec![
                 ControlFileEntry {
                     file_path: "ABC".to_owned(),
                     sha256: str_hash("ABC"),
+                    stat: None,
+                    metadata: None,
                 },
                 ControlFileEntry {
                     file_path: "DEF".to_owned(),
                     sha256: str_hash("DEF"),
+                    stat: None,
+                    metadata: None,
                 },
             ]
         };
@@ -320,14 +630,19 @@ mod tests {
     #[test]
     fn can_get_by_file_path() {
         let cf = ControlFile {
+            header: Header::default(),
             entries: vec![
                 ControlFileEntry {
                     file_path: "ABC".to_owned(),
                     sha256: str_hash("ABC"),
+                    stat: None,
+                    metadata: None,
                 },
                 ControlFileEntry {
                     file_path: "foo/DEF".to_owned(),
                     sha256: str_hash("DEF"),
+                    stat: None,
+                    metadata: None,
                 },
             ]
         };
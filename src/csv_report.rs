@@ -0,0 +1,87 @@
+use std::io::{self, Write};
+
+use crate::{control_file::ControlFileEntry, output::{Renderer, Run}, report::Report, scan_log::ScanLog};
+
+const HEADER: [&str; 10] = [
+    "type", "path", "old_path", "old_hash", "new_hash", "old_size", "new_size", "old_mtime", "new_mtime", "error",
+];
+
+/// Writes one row per change and per unreadable file. Use b',' for CSV and b'\t' for TSV.
+/// Fields that contain the delimiter, quotes or line breaks are quoted so that any file name can be read back.
+pub fn write<W: Write>(w: W, report: &Report, log: &ScanLog, delimiter: u8) -> io::Result<()> {
+    let mut out = csv::WriterBuilder::new().delimiter(delimiter).from_writer(w);
+    out.write_record(HEADER)?;
+
+    for c in report.changes().iter() {
+        let (old_size, old_mtime) = stat_of(report.from.get(c.old_path));
+        let (new_size, new_mtime) = stat_of(report.to.get(c.path));
+        out.write_record([
+            c.kind, c.path, c.old_path,
+            c.old_hash.as_deref().unwrap_or(""), c.new_hash.as_deref().unwrap_or(""),
+            &old_size, &new_size, &old_mtime, &new_mtime, "",
+        ])?;
+    }
+    for e in log.errors.iter() {
+        let prev = report.from.get(&e.path);
+        let hash = prev.map(|p| hex::encode(&p.sha256)).unwrap_or_default();
+        let (size, mtime) = stat_of(prev);
+        out.write_record(["error", &e.path, &e.path, &hash, "", &size, "", &mtime, "", &e.message])?;
+    }
+
+    out.flush()
+}
+
+/// Size and modification time, or empty strings if unknown.
+fn stat_of(e: Option<&ControlFileEntry>) -> (String, String) {
+    match e.and_then(|e| e.stat.as_ref()) {
+        Some(s) => (s.size.to_string(), s.mtime()),
+        None => (String::new(), String::new()),
+    }
+}
+
+/// Writes with the delimiter. See write().
+pub struct CsvRenderer<W: Write>(pub W, pub u8);
+
+impl<W: Write> Renderer for CsvRenderer<W> {
+    fn render(&mut self, report: &Report, log: &ScanLog, _run: &Run) -> io::Result<()> {
+        write(&mut self.0, report, log, self.1)?;
+        self.0.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control_file::{ControlFile, Header, entry, str_hash};
+    use crate::{report::Report, scan_log::{ScanLog, ScanError}};
+
+    #[test]
+    fn can_write_csv() {
+        let from = ControlFile { header: Header::default(), entries: vec![entry("a,\"b\"\nc", "ABC", Some(1))] };
+        let to = ControlFile { header: Header::default(), entries: vec![entry("a,\"b\"\nc", "ABCD", Some(1))] };
+        let report = Report::new(&from, &to);
+        let log = ScanLog {
+            errors: vec![ScanError { path: "x\ty".to_owned(), message: "denied".to_owned() }],
+            ..ScanLog::default()
+        };
+
+        let mut buf = vec![];
+        super::write(&mut buf, &report, &log, b',').unwrap();
+        let mut rows = csv::Reader::from_reader(buf.as_slice());
+        let rows: Vec<csv::StringRecord> = rows.records().map(|r| r.unwrap()).collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(&rows[0][0], "modified");
+        assert_eq!(&rows[0][1], "a,\"b\"\nc");
+        assert_eq!(&rows[0][3], hex::encode(str_hash("ABC")));
+        assert_eq!(&rows[0][5], "3");
+        assert_eq!(&rows[0][6], "4");
+        assert_eq!(&rows[0][7], "1.000000000");
+        assert_eq!(&rows[1][0], "error");
+        assert_eq!(&rows[1][9], "denied");
+
+        let mut buf = vec![];
+        super::write(&mut buf, &report, &log, b'\t').unwrap();
+        let mut rows = csv::ReaderBuilder::new().delimiter(b'\t').from_reader(buf.as_slice());
+        let rows: Vec<csv::StringRecord> = rows.records().map(|r| r.unwrap()).collect();
+        assert_eq!(&rows[1][1], "x\ty");
+    }
+}
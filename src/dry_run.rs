@@ -0,0 +1,99 @@
+use std::fs;
+
+use crate::{io_error::IoError, root::Root, scan_log::Excluded, tree::{self, WalkEntry}, Cli};
+
+/// Result of walking the roots with the exclude rules without hashing.
+#[derive(Debug, Default)]
+pub struct DryRun {
+    pub included: Vec<String>,
+    pub excluded: Vec<Excluded>,
+    pub included_files: usize,
+    pub included_bytes: u64,
+    pub excluded_files: usize,
+    pub excluded_bytes: u64,
+    /// Excluded directories are not walked so that files under them are not counted.
+    pub excluded_dirs: usize,
+}
+
+impl DryRun {
+    pub fn walk(roots: &[Root], cli: &Cli) -> Result<Self, IoError> {
+        let mut ret = Self::default();
+        for r in roots.iter() {
+            let prefix = r.prefix();
+            tree::walk(&r.path, cli, |e| {
+                let (path, rule) = match e {
+                    WalkEntry::Found(path) => (path, None),
+                    WalkEntry::Excluded(path, rule) => (path, Some(rule)),
+                    WalkEntry::Skipped(path, reason) => (path, Some(reason.to_string())),
+                };
+                let size = if path.ends_with('/') {
+                    None
+                } else {
+                    Some(fs::symlink_metadata(r.path.join(&path)).map(|m| m.len()).unwrap_or(0))
+                };
+                let path = format!("{}{}", prefix, path);
+                match rule {
+                    None => {
+                        if let Some(size) = size {
+                            ret.included_files += 1;
+                            ret.included_bytes += size;
+                        }
+                        ret.included.push(path);
+                    },
+                    Some(rule) => {
+                        match size {
+                            Some(size) => {
+                                ret.excluded_files += 1;
+                                ret.excluded_bytes += size;
+                            },
+                            None => ret.excluded_dirs += 1,
+                        }
+                        ret.excluded.push(Excluded { path, rule });
+                    },
+                }
+                Ok(())
+            })?;
+        }
+        ret.included.sort();
+        ret.excluded.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::tempdir;
+    use crate::{Cli, root::Root, scan_log::Excluded};
+    use super::DryRun;
+
+    #[test]
+    fn can_dry_run() {
+        let tmp_dir = tempdir().unwrap();
+        fs::create_dir(tmp_dir.path().join("cache")).unwrap();
+        fs::write(tmp_dir.path().join("cache/a.bin"), b"0123456789").unwrap();
+        fs::write(tmp_dir.path().join("a.txt"), b"012").unwrap();
+        fs::write(tmp_dir.path().join("b.tmp"), b"01234").unwrap();
+
+        let cli = Cli {
+            exclude: vec!["cache/".to_owned(), "*.tmp".to_owned()],
+            ..Cli::default()
+        };
+        let dry_run = DryRun::walk(&[Root::new("", tmp_dir.path())], &cli).unwrap();
+
+        assert_eq!(dry_run.included, vec!["a.txt"]);
+        assert_eq!(dry_run.included_files, 1);
+        assert_eq!(dry_run.included_bytes, 3);
+        assert_eq!(
+            dry_run.excluded,
+            vec![
+                Excluded { path: "b.tmp".to_owned(), rule: "-X '*.tmp'".to_owned() },
+                Excluded { path: "cache/".to_owned(), rule: "-X 'cache/'".to_owned() },
+            ]
+        );
+        assert_eq!(dry_run.excluded_files, 1);
+        assert_eq!(dry_run.excluded_bytes, 5);
+        assert_eq!(dry_run.excluded_dirs, 1);
+    }
+}
@@ -0,0 +1,78 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::{control_file::ControlFileEntry, output::{Renderer, Run}, report::{Change, Report, Summary}, root::Root, scan_log::ScanLog};
+
+/// Progress of a run reported while it is going. Serialized as one JSON object per line with the 'event' field.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    ScanStarted {
+        version: &'static str,
+        /// Seconds since the Unix epoch.
+        started_at: u64,
+        control_file: &'a str,
+        roots: &'a [Root],
+    },
+    FileHashed {
+        path: &'a str,
+        hash: String,
+        #[serde(skip)]
+        entry: &'a ControlFileEntry,
+    },
+    FileChanged(Change<'a>),
+    Error { path: &'a str, message: &'a str },
+    ScanFinished {
+        /// Seconds since the Unix epoch.
+        finished_at: u64,
+        summary: Summary,
+        errors: usize,
+    },
+}
+
+/// Writes the event as a line of JSON and flushes it so that readers receive it immediately.
+pub fn write_ndjson<W: Write>(w: &mut W, event: &Event) -> io::Result<()> {
+    serde_json::to_writer(&mut *w, event)?;
+    writeln!(w)?;
+    w.flush()
+}
+
+/// Writes events as they happen. The report after the scan is not written since it is in the events.
+pub struct NdjsonRenderer<W: Write>(pub W);
+
+impl<W: Write> Renderer for NdjsonRenderer<W> {
+    fn event(&mut self, event: &Event) -> io::Result<()> {
+        write_ndjson(&mut self.0, event)
+    }
+
+    fn render(&mut self, _report: &Report, _log: &ScanLog, _run: &Run) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use crate::{control_file::ControlFileEntry, report::Change};
+    use super::{Event, write_ndjson};
+
+    #[test]
+    fn can_write_ndjson() {
+        let mut buf = vec![];
+        let entry = ControlFileEntry { file_path: "a/b".to_owned(), sha256: vec![0], stat: None, metadata: None };
+        write_ndjson(&mut buf, &Event::FileHashed { path: "a/b", hash: "00".to_owned(), entry: &entry }).unwrap();
+        write_ndjson(&mut buf, &Event::FileChanged(Change::new("added", "c", "c", None, None))).unwrap();
+
+        let lines: Vec<Value> = buf.split(|b| *b == b'\n').filter(|l| ! l.is_empty())
+            .map(|l| serde_json::from_slice(l).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["event"], "file_hashed");
+        assert_eq!(lines[0]["path"], "a/b");
+        assert_eq!(lines[0]["hash"], "00");
+        assert_eq!(lines[0].get("entry"), None);
+        assert_eq!(lines[1]["event"], "file_changed");
+        assert_eq!(lines[1]["kind"], "added");
+        assert_eq!(lines[1]["new_hash"], Value::Null);
+    }
+}
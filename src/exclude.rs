@@ -1,41 +1,345 @@
+use std::{fs::{self, Metadata}, io::Error, path::Path, sync::Arc, time::SystemTime};
 
 use wildmatch::WildMatch;
 
+use crate::{rule::Rule, Cli};
+
+/// Ignore file that is honored in any directory under the target. Its patterns are scoped to the directory.
+pub const IGNORE_FILE_NAME: &str = ".scrubignore";
+
+/// Exclude patterns with gitignore semantics. Patterns are matched against paths relative to the target root.
+/// Patterns read from an ignore file form a layer on top of the patterns of the parent directories.
 pub struct Exclude {
-    globs: Vec<WildMatch>,
+    /// Directory of the ignore file relative to the target root ending with '/'. Empty for the root.
+    base: String,
+    patterns: Vec<Pattern>,
+    /// Rules given in the command line. Only the root layer has them.
+    rules: Vec<Rule>,
+    parent: Option<Arc<Exclude>>,
 }
 
 impl Exclude {
     pub fn new(args: Vec<String>) -> Self {
-        Self { globs: args.iter().map(|e| WildMatch::new(&e)).collect() }
+        Self {
+            base: String::new(), patterns: args.iter().map(|e| Pattern::parse(e, "-X".to_owned())).collect(),
+            rules: vec![], parent: None
+        }
+    }
+
+    /// Patterns of presets, '--exclude-from' files and '-X' in this order, and rules in the command line.
+    /// Presets must have been resolved by `Cli::resolve_presets`.
+    pub fn from_cli(cli: &Cli) -> Result<Self, Error> {
+        let mut patterns: Vec<Pattern> = vec![];
+        for (p, origin) in cli.preset_patterns.iter() {
+            patterns.push(Pattern::parse(p, origin.clone()));
+        }
+        for f in cli.exclude_from.iter() {
+            patterns.append(&mut read_patterns(Path::new(f), f)?);
+        }
+        patterns.append(&mut Self::new(cli.exclude.clone()).patterns);
+
+        let mut rules: Vec<Rule> = vec![];
+        for r in cli.exclude_regex.iter() {
+            rules.push(Rule::Regex(r.clone()));
+        }
+        if let Some(size) = cli.exclude_larger_than {
+            rules.push(Rule::LargerThan(size));
+        }
+        if let Some(duration) = cli.exclude_newer_than {
+            let since = SystemTime::now().checked_sub(duration).ok_or_else(||
+                Error::other(format!("Duration {:?} is too large.", duration))
+            )?;
+            rules.push(Rule::NewerThan(since, duration));
+        }
+        for kind in cli.exclude_type.iter() {
+            rules.push(Rule::Kind(*kind));
+        }
+
+        Ok(Self { base: String::new(), patterns, rules, parent: None })
+    }
+
+    /// Reads an ignore file in the directory `base` (relative to the target root, ending with '/' or empty).
+    pub fn read_ignore_file(path: &Path, base: &str, parent: Arc<Exclude>) -> Result<Self, Error> {
+        let origin = format!("{}{}", base, IGNORE_FILE_NAME);
+        Ok(Self { base: base.to_owned(), patterns: read_patterns(path, &origin)?, rules: vec![], parent: Some(parent) })
+    }
+
+    pub fn has_rules(&self) -> bool {
+        ! self.root().rules.is_empty()
+    }
+
+    /// The rule that excludes the path. Rules apply even to paths re-included by negated patterns.
+    /// `meta` follows symbolic links whereas `link_meta` does not.
+    pub fn matching_rule(&self, path: &str, meta: &Metadata, link_meta: &Metadata) -> Option<&Rule> {
+        self.root().rules.iter().find(|r| r.matches(path, meta, link_meta))
+    }
+
+    fn root(&self) -> &Exclude {
+        match &self.parent {
+            Some(p) => p.root(),
+            None => self,
+        }
+    }
+
+    /// The last matching pattern decides. Negated pattern ('!') re-includes the path.
+    #[cfg(test)]
+    pub fn matches(&self, path: &str, is_dir: bool) -> bool {
+        self.matching(path, is_dir).map(|p| p.excludes()).unwrap_or(false)
+    }
+
+    /// The pattern that decides whether the path is excluded. Patterns in deeper ignore files take precedence.
+    pub fn matching(&self, path: &str, is_dir: bool) -> Option<&Pattern> {
+        let found = path.strip_prefix(&self.base).and_then(|rest| {
+            let segments: Vec<&str> = rest.trim_end_matches('/').split('/').collect();
+            self.patterns.iter().rev().find(|p| p.matches(&segments, is_dir))
+        });
+        found.or_else(|| self.parent.as_ref().and_then(|p| p.matching(path, is_dir)))
+    }
+}
+
+/// Include patterns. When any is given, only files that match one of them are scrubbed.
+/// Directories are always walked. Exclude patterns take precedence over include patterns.
+pub struct Include {
+    patterns: Exclude,
+}
+
+impl Include {
+    pub fn new(args: &[String]) -> Self {
+        Self {
+            patterns: Exclude {
+                base: String::new(), patterns: args.iter().map(|e| Pattern::parse(e, "-I".to_owned())).collect(),
+                rules: vec![], parent: None
+            }
+        }
+    }
+
+    pub fn includes(&self, path: &str, is_dir: bool) -> bool {
+        is_dir || self.patterns.patterns.is_empty() || self.patterns.matching(path, false).map(|p| p.excludes()).unwrap_or(false)
+    }
+}
+
+fn read_patterns(path: &Path, origin: &str) -> Result<Vec<Pattern>, Error> {
+    Ok(
+        fs::read_to_string(path)?.lines().enumerate()
+            .map(|(idx, l)| (idx, l.trim_end()))
+            .filter(|(_, l)| ! l.is_empty() && ! l.starts_with('#'))
+            .map(|(idx, l)| Pattern::parse(l, format!("{}:{}", origin, idx + 1)))
+            .collect()
+    )
+}
+
+enum Segment {
+    /// '**' that matches zero or more directories.
+    AnyDirs,
+    Glob(WildMatch),
+}
+
+pub struct Pattern {
+    segments: Vec<Segment>,
+    negated: bool,
+    dir_only: bool,
+    text: String,
+    /// Where the pattern came from such as '-X' or 'path/.scrubignore:3'.
+    origin: String,
+}
+
+impl Pattern {
+    fn parse(text: &str, origin: String) -> Self {
+        let s = text;
+        let (negated, s) = match s.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let (dir_only, s) = match s.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        // A pattern with a separator is anchored to the root. Otherwise it matches at any level.
+        let anchored = s.contains('/');
+        let mut segments: Vec<Segment> = s.trim_start_matches('/').split('/').map(|seg|
+            if seg == "**" { Segment::AnyDirs } else { Segment::Glob(WildMatch::new(seg)) }
+        ).collect();
+        if ! anchored {
+            segments.insert(0, Segment::AnyDirs);
+        }
+
+        Self { segments, negated, dir_only, text: text.to_owned(), origin }
+    }
+
+    pub fn excludes(&self) -> bool {
+        ! self.negated
+    }
+
+    pub fn describe(&self) -> String {
+        format!("{} '{}'", self.origin, self.text)
     }
 
-    pub fn matches(&self, s: &str) -> bool {
-        self.globs.iter().position(|p| p.matches(s)).is_some()
+    fn matches(&self, path: &[&str], is_dir: bool) -> bool {
+        if self.dir_only && ! is_dir {
+            return false;
+        }
+        match_segments(&self.segments, path)
+    }
+}
+
+fn match_segments(pattern: &[Segment], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        // Trailing '**' matches everything inside, but not the directory itself.
+        Some((Segment::AnyDirs, [])) => ! path.is_empty(),
+        Some((Segment::AnyDirs, rest)) => (0..=path.len()).any(|i| match_segments(rest, &path[i..])),
+        Some((Segment::Glob(glob), rest)) =>
+            ! path.is_empty() && glob.matches(path[0]) && match_segments(rest, &path[1..]),
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
-    use super::Exclude;
+    use std::{fs, sync::Arc, time::Duration};
+    use tempfile::tempdir;
+    use crate::Cli;
+    use super::{Exclude, Include};
+
+    fn ex(patterns: &[&str]) -> Exclude {
+        Exclude::new(patterns.iter().map(|p| p.to_string()).collect())
+    }
 
     #[test]
     fn empty() {
         let ex = Exclude::new(vec![]);
-        assert_eq!(ex.matches("ABC"), false);
+        assert_eq!(ex.matches("ABC", false), false);
     }
 
     #[test]
     fn single_pattern() {
         let ex = Exclude::new(vec![".*".to_owned()]);
-        assert_eq!(ex.matches(".DS_STORE"), true);
-        assert_eq!(ex.matches("A.exe"), false);
+        assert_eq!(ex.matches(".DS_STORE", false), true);
+        assert_eq!(ex.matches("A.exe", false), false);
     }
     #[test]
     fn few_patterns() {
         let ex = Exclude::new(vec![".*".to_owned(), "*~".to_owned()]);
-        assert_eq!(ex.matches(".DS_STORE"), true);
-        assert_eq!(ex.matches("A~"), true);
-        assert_eq!(ex.matches("A.exe"), false);
+        assert_eq!(ex.matches(".DS_STORE", false), true);
+        assert_eq!(ex.matches("A~", false), true);
+        assert_eq!(ex.matches("A.exe", false), false);
+    }
+
+    #[test]
+    fn unanchored_matches_any_level() {
+        let ex = ex(&["cache"]);
+        assert!(ex.matches("cache", true));
+        assert!(ex.matches("photos/cache", true));
+        assert!(ex.matches("a/b/cache", false));
+        assert!(!ex.matches("cache2", false));
+    }
+
+    #[test]
+    fn anchored() {
+        let ex = ex(&["photos/cache", "/top.txt"]);
+        assert!(ex.matches("photos/cache", true));
+        assert!(!ex.matches("videos/cache", true));
+        assert!(!ex.matches("a/photos/cache", true));
+        assert!(ex.matches("top.txt", false));
+        assert!(!ex.matches("a/top.txt", false));
+    }
+
+    #[test]
+    fn double_asterisk() {
+        let ex = ex(&["**/logs", "a/**/b", "out/**"]);
+        assert!(ex.matches("logs", true));
+        assert!(ex.matches("x/y/logs", true));
+        assert!(ex.matches("a/b", false));
+        assert!(ex.matches("a/x/y/b", false));
+        assert!(!ex.matches("out", true));
+        assert!(ex.matches("out/x", false));
+        assert!(ex.matches("out/x/y", false));
+    }
+
+    #[test]
+    fn dir_only() {
+        let ex = ex(&["build/"]);
+        assert!(ex.matches("build", true));
+        assert!(ex.matches("x/build/", true));
+        assert!(!ex.matches("build", false));
+    }
+
+    #[test]
+    fn negation() {
+        let ex = ex(&["*.log", "!keep.log", "!/a/*.tmp", "a/x.tmp"]);
+        assert!(ex.matches("foo.log", false));
+        assert!(!ex.matches("keep.log", false));
+        assert!(!ex.matches("dir/keep.log", false));
+        assert!(ex.matches("a/x.tmp", false));
+        assert!(!ex.matches("a/y.tmp", false));
+    }
+
+    #[test]
+    fn can_read_files() {
+        let tmp_dir = tempdir().unwrap();
+        let from = tmp_dir.path().join("excludes");
+        fs::write(&from, "# comment\n\n*.tmp\n!keep.tmp\n").unwrap();
+        let ignore = tmp_dir.path().join(".scrubignore");
+        fs::write(&ignore, "/cache/\nkeep.tmp\n").unwrap();
+
+        let cli = Cli {
+            exclude_from: vec![from.to_string_lossy().to_string()],
+            exclude: vec!["*.bak".to_owned()],
+            ..Cli::default()
+        };
+        let root = Exclude::from_cli(&cli).unwrap();
+        assert!(root.matches("a.tmp", false));
+        assert!(!root.matches("keep.tmp", false));
+        assert_eq!(root.matching("x/a.bak", false).unwrap().describe(), "-X '*.bak'");
+
+        let scoped = Exclude::read_ignore_file(&ignore, "photos/", Arc::new(root)).unwrap();
+        assert!(scoped.matches("photos/cache", true));
+        assert!(!scoped.matches("cache", true));
+        assert!(scoped.matches("photos/keep.tmp", false));
+        assert!(!scoped.matches("keep.tmp", false));
+        assert_eq!(scoped.matching("photos/cache", true).unwrap().describe(), "photos/.scrubignore:1 '/cache/'");
+        assert_eq!(
+            scoped.matching("a.tmp", false).unwrap().describe(),
+            format!("{}:3 '*.tmp'", from.to_string_lossy())
+        );
+    }
+
+    #[test]
+    fn rejects_too_large_duration() {
+        let cli = Cli { exclude_newer_than: Some(Duration::MAX), ..Cli::default() };
+        assert!(Exclude::from_cli(&cli).is_err());
+
+        let cli = Cli { exclude_newer_than: Some(Duration::from_secs(600)), ..Cli::default() };
+        assert!(Exclude::from_cli(&cli).unwrap().has_rules());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn can_use_presets() {
+        let mut cli = Cli {
+            preset: vec!["macos".to_owned(), "vcs".to_owned()],
+            exclude: vec!["!.git/".to_owned()],
+            ..Cli::default()
+        };
+        cli.resolve_presets().unwrap();
+        let ex = Exclude::from_cli(&cli).unwrap();
+        assert!(ex.matches("photos/.DS_Store", false));
+        assert_eq!(ex.matching(".hg", true).unwrap().describe(), "--preset vcs '.hg/'");
+        assert!(!ex.matches(".git", true));
+
+        let mut cli = Cli { preset: vec!["nope".to_owned()], ..Cli::default() };
+        assert!(cli.resolve_presets().unwrap_err().to_string().starts_with("Unknown preset 'nope'."));
+    }
+
+    #[test]
+    fn include() {
+        let all = Include::new(&[]);
+        assert!(all.includes("a.jpg", false));
+
+        let raw = Include::new(&["*.raw".to_owned(), "*.flac".to_owned(), "!tmp/**".to_owned()]);
+        assert!(raw.includes("a.raw", false));
+        assert!(raw.includes("x/a.flac", false));
+        assert!(!raw.includes("a.jpg", false));
+        assert!(!raw.includes("tmp/a.raw", false));
+        assert!(raw.includes("photos", true));
+    }
+}
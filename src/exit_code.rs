@@ -0,0 +1,81 @@
+use clap::ValueEnum;
+
+use crate::report::Summary;
+
+pub const CLEAN: i32 = 0;
+/// The scan was aborted, e.g. the target directory does not exist.
+pub const FATAL: i32 = 1;
+/// Invalid command line arguments.
+pub const USAGE: i32 = 2;
+pub const SUSPECTED_CORRUPTION: i32 = 3;
+pub const SCAN_ERRORS: i32 = 4;
+pub const REMOVED: i32 = 5;
+pub const MODIFIED: i32 = 6;
+pub const ADDED_ONLY: i32 = 7;
+
+/// Class of outcome that can be counted as failure by '--fail-on'. Listed in the order of severity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Outcome {
+    /// Content changed without changing size or modification time.
+    Corruption,
+    /// Some files could not be read.
+    Errors,
+    /// Files or directories were removed.
+    Removed,
+    /// Content or metadata of files changed, or files were moved or renamed.
+    Modified,
+    /// Files or directories were added.
+    Added,
+}
+
+impl Outcome {
+    pub const ALL: [Outcome; 5] = [Outcome::Corruption, Outcome::Errors, Outcome::Removed, Outcome::Modified, Outcome::Added];
+
+    fn exit_code(self) -> i32 {
+        match self {
+            Outcome::Corruption => SUSPECTED_CORRUPTION,
+            Outcome::Errors => SCAN_ERRORS,
+            Outcome::Removed => REMOVED,
+            Outcome::Modified => MODIFIED,
+            Outcome::Added => ADDED_ONLY,
+        }
+    }
+
+    fn occurred(self, summary: &Summary, errors: usize) -> bool {
+        match self {
+            Outcome::Corruption => 0 < summary.corrupted,
+            Outcome::Errors => 0 < errors,
+            Outcome::Removed => 0 < summary.removed + summary.removed_dirs,
+            Outcome::Modified => 0 < summary.modified + summary.metadata_changed + summary.moved + summary.normalization_matches,
+            Outcome::Added => 0 < summary.added + summary.added_dirs,
+        }
+    }
+}
+
+/// Exit code of the most severe outcome that occurred among the ones counted as failure. CLEAN if none occurred.
+pub fn of(fail_on: &[Outcome], summary: &Summary, errors: usize) -> i32 {
+    Outcome::ALL.iter()
+        .find(|o| fail_on.contains(o) && o.occurred(summary, errors))
+        .map(|o| o.exit_code())
+        .unwrap_or(CLEAN)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::report::Summary;
+    use super::{of, Outcome, ADDED_ONLY, CLEAN, MODIFIED, SCAN_ERRORS, SUSPECTED_CORRUPTION};
+
+    #[test]
+    fn can_decide_exit_code() {
+        let all = Outcome::ALL;
+        assert_eq!(of(&all, &Summary::default(), 0), CLEAN);
+        assert_eq!(of(&all, &Summary { added: 1, ..Summary::default() }, 0), ADDED_ONLY);
+        assert_eq!(of(&all, &Summary { added: 1, moved: 1, ..Summary::default() }, 0), MODIFIED);
+        assert_eq!(of(&all, &Summary { modified: 1, corrupted: 1, ..Summary::default() }, 1), SUSPECTED_CORRUPTION);
+        assert_eq!(of(&all, &Summary { removed: 1, ..Summary::default() }, 1), SCAN_ERRORS);
+
+        let fail_on = [Outcome::Corruption, Outcome::Modified];
+        assert_eq!(of(&fail_on, &Summary { added: 1, removed: 1, ..Summary::default() }, 1), CLEAN);
+        assert_eq!(of(&fail_on, &Summary { added: 1, modified: 1, ..Summary::default() }, 1), MODIFIED);
+    }
+}
@@ -0,0 +1,238 @@
+use std::{collections::BTreeMap, io::{self, Write}};
+
+use crate::{control_file::ControlFileEntry, output::{Renderer, Run}, report::{Change, Report, CORRUPTED, MODIFIED}, scan_log::ScanLog};
+
+const STYLE: &str = r#"
+body { font-family: sans-serif; margin: 2em; color: #222; }
+h1 { font-size: 1.5em; }
+.run { color: #666; }
+.cards { display: flex; flex-wrap: wrap; gap: 0.5em; }
+.card { border: 1px solid #ccc; border-radius: 4px; padding: 0.5em 1em; min-width: 8em; }
+.card .count { font-size: 1.8em; font-weight: bold; }
+.card.alert { border-color: #c00; background: #fee; color: #c00; }
+.card.zero { color: #aaa; }
+table { border-collapse: collapse; margin: 0.5em 0 1.5em; }
+th, td { border: 1px solid #ddd; padding: 0.2em 0.5em; text-align: left; font-size: 0.9em; }
+th { background: #f4f4f4; cursor: pointer; user-select: none; }
+td.hash { font-family: monospace; font-size: 0.8em; }
+details { margin-left: 1em; }
+summary { cursor: pointer; }
+.kind { display: inline-block; min-width: 9em; font-size: 0.85em; }
+.corrupted, .removed, .error { color: #c00; font-weight: bold; }
+.modified { color: #b60; }
+.added { color: #080; }
+"#;
+
+/// Sorts rows of a table by the clicked column. Clicking the same column again reverses the order.
+const SCRIPT: &str = r#"
+document.querySelectorAll("table.sortable th").forEach(function(th) {
+  th.addEventListener("click", function() {
+    var table = th.closest("table");
+    var idx = Array.prototype.indexOf.call(th.parentNode.children, th);
+    var asc = th.dataset.order !== "asc";
+    th.parentNode.querySelectorAll("th").forEach(function(h) { delete h.dataset.order; });
+    th.dataset.order = asc ? "asc" : "desc";
+    var body = table.tBodies[0];
+    var rows = Array.prototype.slice.call(body.rows);
+    rows.sort(function(a, b) {
+      var x = a.cells[idx].textContent, y = b.cells[idx].textContent;
+      var nx = parseFloat(x), ny = parseFloat(y);
+      var c = (!isNaN(nx) && !isNaN(ny)) ? nx - ny : x.localeCompare(y);
+      return asc ? c : -c;
+    });
+    rows.forEach(function(r) { body.appendChild(r); });
+  });
+});
+"#;
+
+/// Changes under a directory. Used to render collapsible trees.
+#[derive(Default)]
+struct Dir<'a> {
+    dirs: BTreeMap<&'a str, Dir<'a>>,
+    /// Kind of change and the file name.
+    files: Vec<(&'static str, &'a str)>,
+    count: usize,
+}
+
+impl<'a> Dir<'a> {
+    fn add(&mut self, kind: &'static str, path: &'a str) {
+        self.count += 1;
+        // Changed directories are listed in their parent with the trailing '/'.
+        match path.strip_suffix('/').unwrap_or(path).find('/') {
+            Some(idx) => self.dirs.entry(&path[..idx]).or_default().add(kind, &path[idx + 1..]),
+            None => self.files.push((kind, path)),
+        }
+    }
+
+    fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        for (name, dir) in self.dirs.iter() {
+            writeln!(w, "<details open><summary>{}/ ({})</summary>", escape(name), dir.count)?;
+            dir.write(w)?;
+            writeln!(w, "</details>")?;
+        }
+        for (kind, name) in self.files.iter() {
+            writeln!(w, r#"<div><span class="kind {}">{}</span> {}</div>"#, kind, kind, escape(name))?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes a single HTML document with styles and scripts embedded so that it can be attached to mails as is.
+pub fn write<W: Write>(w: &mut W, report: &Report, log: &ScanLog, run: &Run) -> io::Result<()> {
+    let changes = report.changes();
+    let summary = report.summary("");
+
+    writeln!(w, "<!DOCTYPE html>")?;
+    writeln!(w, r#"<html><head><meta charset="utf-8"><title>disk_scrub report</title><style>{}</style></head><body>"#, STYLE)?;
+    writeln!(w, "<h1>disk_scrub report</h1>")?;
+    writeln!(
+        w, r#"<p class="run">Version {} / Started {} / Finished {} / Controlfile {}</p>"#,
+        run.version, format_time(run.started_at as i64), format_time(run.finished_at as i64), escape(&run.control_file)
+    )?;
+    for r in run.roots.iter() {
+        let name = if r.name.is_empty() { String::new() } else { format!("{}: ", r.name) };
+        writeln!(w, r#"<p class="run">Root {}{}</p>"#, escape(&name), escape(&r.path.to_string_lossy()))?;
+    }
+
+    writeln!(w, "<h2>Summary</h2>")?;
+    writeln!(w, r#"<div class="cards">"#)?;
+    let cards = [
+        ("Suspected corruption", summary.corrupted, true),
+        ("Unreadable files", log.errors.len(), true),
+        ("Removed files", summary.removed, false),
+        ("Modified files", summary.modified, false),
+        ("Added files", summary.added, false),
+        ("Metadata changed files", summary.metadata_changed, false),
+        ("Moved/renamed files", summary.moved, false),
+        ("Normalization/case matches", summary.normalization_matches, false),
+        ("Added directories", summary.added_dirs, false),
+        ("Removed directories", summary.removed_dirs, false),
+    ];
+    for (label, count, alert) in cards {
+        let class = if count == 0 { "card zero" } else if alert { "card alert" } else { "card" };
+        writeln!(w, r#"<div class="{}"><div class="count">{}</div>{}</div>"#, class, count, label)?;
+    }
+    writeln!(w, "</div>")?;
+
+    writeln!(w, "<h2>Changes by directory</h2>")?;
+    let mut root = Dir::default();
+    for c in changes.iter() {
+        root.add(c.kind, c.path);
+    }
+    root.write(w)?;
+
+    writeln!(w, "<h2>Modified files</h2>")?;
+    writeln!(w, r#"<table class="sortable"><thead><tr>"#)?;
+    for h in ["Path", "Kind", "Old size", "New size", "Old mtime", "New mtime", "Old metadata", "New metadata", "Old hash", "New hash"] {
+        write!(w, "<th>{}</th>", h)?;
+    }
+    writeln!(w, "</tr></thead><tbody>")?;
+    for c in changes.iter().filter(|c| c.kind == MODIFIED || c.kind == CORRUPTED || c.kind == "metadata_changed") {
+        write_modified_row(w, report, c)?;
+    }
+    writeln!(w, "</tbody></table>")?;
+
+    writeln!(w, "<h2>All changes</h2>")?;
+    writeln!(w, r#"<table class="sortable"><thead><tr><th>Kind</th><th>Path</th><th>Old path</th><th>Old hash</th><th>New hash</th></tr></thead><tbody>"#)?;
+    for c in changes.iter() {
+        writeln!(
+            w, r#"<tr><td class="{}">{}</td><td>{}</td><td>{}</td><td class="hash">{}</td><td class="hash">{}</td></tr>"#,
+            c.kind, c.kind, escape(c.path), escape(c.old_path),
+            c.old_hash.as_deref().unwrap_or(""), c.new_hash.as_deref().unwrap_or("")
+        )?;
+    }
+    writeln!(w, "</tbody></table>")?;
+
+    if ! log.errors.is_empty() {
+        writeln!(w, "<h2>Unreadable files</h2>")?;
+        writeln!(w, r#"<table class="sortable"><thead><tr><th>Path</th><th>Error</th></tr></thead><tbody>"#)?;
+        for e in log.errors.iter() {
+            writeln!(w, r#"<tr><td class="error">{}</td><td>{}</td></tr>"#, escape(&e.path), escape(&e.message))?;
+        }
+        writeln!(w, "</tbody></table>")?;
+    }
+
+    writeln!(w, "<script>{}</script>", SCRIPT)?;
+    writeln!(w, "</body></html>")
+}
+
+pub struct HtmlRenderer<W: Write>(pub W);
+
+impl<W: Write> Renderer for HtmlRenderer<W> {
+    fn render(&mut self, report: &Report, log: &ScanLog, run: &Run) -> io::Result<()> {
+        write(&mut self.0, report, log, run)?;
+        self.0.flush()
+    }
+}
+
+fn write_modified_row<W: Write>(w: &mut W, report: &Report, c: &Change) -> io::Result<()> {
+    let old = report.from.get(c.old_path);
+    let new = report.to.get(c.path);
+    let size = |e: Option<&ControlFileEntry>| e.and_then(|e| e.stat.as_ref()).map(|s| s.size.to_string()).unwrap_or_default();
+    let mtime = |e: Option<&ControlFileEntry>| {
+        e.and_then(|e| e.stat.as_ref()).map(|s| format!("{}.{:09}", format_time(s.mtime_sec), s.mtime_nsec)).unwrap_or_default()
+    };
+    let meta = |e: Option<&ControlFileEntry>| {
+        e.and_then(|e| e.metadata.as_ref()).map(|m| format!("{:o} {}:{}", m.mode, m.uid, m.gid)).unwrap_or_default()
+    };
+    writeln!(
+        w, r#"<tr><td>{}</td><td class="{}">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class="hash">{}</td><td class="hash">{}</td></tr>"#,
+        escape(c.path), c.kind, c.kind, size(old), size(new), mtime(old), mtime(new), meta(old), meta(new),
+        c.old_hash.as_deref().unwrap_or(""), c.new_hash.as_deref().unwrap_or("")
+    )
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&#39;")
+}
+
+/// Formats seconds since the Unix epoch as 'YYYY-MM-DD hh:mm:ss' in UTC.
+fn format_time(secs: i64) -> String {
+    let days = secs.div_euclid(86400);
+    let rem = secs.rem_euclid(86400);
+    // Converts days to a civil date. See http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", y, m, d, rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+    use crate::control_file::{ControlFile, Header, entry};
+    use crate::{output::Run, report::Report, root::Root, scan_log::ScanLog};
+    use super::{format_time, write};
+
+    #[test]
+    fn can_write_html() {
+        let from = ControlFile { header: Header::default(), entries: vec![entry("a/b/c<1>", "abc", Some(0)), entry("a/d", "d", Some(0))] };
+        let to = ControlFile { header: Header::default(), entries: vec![entry("a/b/c<1>", "abcd", Some(86400)), entry("e", "e", Some(0))] };
+        let report = Report::new(&from, &to);
+        let run = Run::new(SystemTime::now(), "Controlfile", &[Root::new("", "/tmp")], false);
+
+        let mut buf = vec![];
+        write(&mut buf, &report, &ScanLog::default(), &run).unwrap();
+        let html = String::from_utf8(buf).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<summary>a/ (2)</summary>"));
+        assert!(html.contains("<summary>b/ (1)</summary>"));
+        assert!(html.contains(r#"<span class="kind modified">modified</span> c&lt;1&gt;"#));
+        assert!(html.contains("<td>3</td><td>4</td><td>1970-01-01 00:00:00.000000000</td><td>1970-01-02 00:00:00.000000000</td>"));
+        assert!(! html.contains("<script src"));
+    }
+
+    #[test]
+    fn can_format_time() {
+        assert_eq!(format_time(0), "1970-01-01 00:00:00");
+        assert_eq!(format_time(951782400), "2000-02-29 00:00:00");
+        assert_eq!(format_time(1700000000), "2023-11-14 22:13:20");
+    }
+}
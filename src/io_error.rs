@@ -1,8 +1,17 @@
-use std::{path::{PathBuf}, io::Error};
+use std::{path::{PathBuf}, io::Error, fmt};
 
 #[derive(Debug)]
 pub struct IoError {
     pub path: Option<PathBuf>,
     pub message: String,
     pub cause: Error,
-}
\ No newline at end of file
+}
+
+impl fmt::Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{} {:?}: {}", self.message, path, self.cause),
+            None => write!(f, "{} {}", self.message, self.cause),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::{output::{Renderer, Run}, report::{Change, Report, Summary}, scan_log::ScanLog};
+
+/// Incremented whenever a field is removed or its meaning changes. Adding fields does not change the version.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Document<'a> {
+    schema_version: u32,
+    run: &'a Run,
+    summary: Summary,
+    roots: Vec<RootSummary<'a>>,
+    changes: Vec<Change<'a>>,
+    case_collisions: &'a Vec<Vec<&'a str>>,
+    scan: &'a ScanLog,
+}
+
+#[derive(Serialize)]
+struct RootSummary<'a> {
+    name: &'a str,
+    summary: Summary,
+}
+
+pub fn write<W: Write>(w: &mut W, report: &Report, log: &ScanLog, run: &Run) -> io::Result<()> {
+    let doc = Document {
+        schema_version: SCHEMA_VERSION,
+        run,
+        summary: report.summary(""),
+        roots: run.roots.iter().map(|r| RootSummary { name: &r.name, summary: report.summary(&r.prefix()) }).collect(),
+        changes: report.changes(),
+        case_collisions: &report.case_collisions,
+        scan: log,
+    };
+    serde_json::to_writer_pretty(&mut *w, &doc)?;
+    writeln!(w)
+}
+
+pub struct JsonRenderer<W: Write>(pub W);
+
+impl<W: Write> Renderer for JsonRenderer<W> {
+    fn render(&mut self, report: &Report, log: &ScanLog, run: &Run) -> io::Result<()> {
+        write(&mut self.0, report, log, run)?;
+        self.0.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+    use serde_json::Value;
+    use crate::control_file::{ControlFile, Header, entry, str_hash};
+    use crate::{output::Run, report::Report, root::Root, scan_log::ScanLog};
+    use super::SCHEMA_VERSION;
+
+    #[test]
+    fn can_write_json() {
+        let from = ControlFile { header: Header::default(), entries: vec![entry("ABC", "ABC", None), entry("DEF", "DEF", None)] };
+        let to = ControlFile { header: Header::default(), entries: vec![entry("DEF", "DEF0", None), entry("GHI", "GHI", None)] };
+        let report = Report::new(&from, &to);
+        let run = Run::new(SystemTime::now(), "Controlfile", &[Root::new("", "/tmp")], false);
+
+        let mut buf = vec![];
+        super::write(&mut buf, &report, &ScanLog::default(), &run).unwrap();
+        let doc: Value = serde_json::from_slice(&buf).unwrap();
+
+        assert_eq!(doc["schema_version"], SCHEMA_VERSION);
+        assert_eq!(doc["run"]["control_file"], "Controlfile");
+        assert_eq!(doc["run"]["roots"][0]["path"], "/tmp");
+        assert_eq!(doc["summary"]["added"], 1);
+        assert_eq!(doc["summary"]["modified"], 1);
+        assert_eq!(doc["roots"][0]["summary"]["removed"], 1);
+
+        let changes = doc["changes"].as_array().unwrap();
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0]["kind"], "added");
+        assert_eq!(changes[0]["path"], "GHI");
+        assert_eq!(changes[0]["old_hash"], Value::Null);
+        assert_eq!(changes[0]["new_hash"], hex::encode(str_hash("GHI")));
+        assert_eq!(changes[2]["kind"], "modified");
+        assert_eq!(changes[2]["old_hash"], hex::encode(str_hash("DEF")));
+        assert_eq!(changes[2]["new_hash"], hex::encode(str_hash("DEF0")));
+    }
+}
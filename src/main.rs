@@ -1,21 +1,52 @@
-use std::path::Path;
+use std::{collections::HashSet, io, path::{Path, PathBuf}, process, time::{Duration, SystemTime}};
 
 use clap::Parser;
-use control_file::ControlFile;
-use report::Report;
+use regex::Regex;
+use control_file::{ControlFile, Header};
+use dry_run::DryRun;
+use exclude::Include;
+use exit_code::Outcome;
+use preset::Presets;
+use output::{OutputFormat, OutputSpec, Renderer, Run};
+use rule::FileKind;
+use event::Event;
+use report::{Change, Report};
+use root::Root;
+use scan_log::ScanLog;
 
 mod tree;
 mod control_file;
 mod report;
 mod io_error;
 mod exclude;
+mod metadata;
+mod unicode;
+mod root;
+mod scan_log;
+mod rule;
+mod marker;
+mod dry_run;
+mod preset;
+mod output;
+mod json_report;
+mod event;
+mod csv_report;
+mod ci_report;
+mod html_report;
+mod exit_code;
+mod text_report;
+mod template_report;
+mod prom_report;
+mod rollup;
 
-#[derive(Parser)]
+#[derive(Parser, Default, Clone)]
 #[clap(author, version, about, long_about = Some("Checks file integrity."))]
 pub struct Cli {
-    /// Target directory to preform integrity check. Will be checked recursively.
-    #[clap(value_parser)]
-    target_dir: String,
+    /// Target directories to preform integrity check. Will be checked recursively.
+    /// You can specify more than one directory as NAME=PATH. Files under each directory are recorded with 'NAME/' prefix.
+    /// If NAME is omitted, the last component of the path is used.
+    #[clap(value_parser, required = true)]
+    target_dir: Vec<String>,
 
     /// Control file location. Will be updated according to the contents under the target directory.
     /// If the tool aborts, this file will not be changed.
@@ -25,65 +56,322 @@ pub struct Cli {
     /// Exclude directories or files that match the specified wildcard pattern.
     /// You can use '?' that matches any single character and '*' that matches more than zero length characters.
     /// '.*' means files or directories that starts with '.' such as '._DS_STORE'.
+    /// Patterns follow gitignore rules and are matched against the path relative to the target directory:
+    /// a pattern containing '/' such as 'photos/cache' is anchored to the target directory, '**' matches any directories,
+    /// a trailing '/' matches only directories and a leading '!' re-includes paths excluded by earlier patterns.
     /// You can more than one exclude pattern.
     #[clap(short = 'X', long, value_parser)]
     exclude: Vec<String>,
+
+    /// Exclude files with named sets of patterns separated by ','. Built-in presets are 'macos', 'windows', 'editors',
+    /// 'vcs' and 'downloads'. Patterns given by -X and --exclude-from take precedence over presets.
+    #[clap(long, value_parser, value_delimiter = ',')]
+    preset: Vec<String>,
+
+    /// File that defines more presets. Each preset starts with '[NAME]' line followed by patterns.
+    /// Defaults to '$XDG_CONFIG_HOME/disk_scrub/presets' (or '~/.config/disk_scrub/presets') if it exists.
+    #[clap(long, value_parser)]
+    preset_file: Option<String>,
+
+    /// Patterns of the presets with their origins. Resolved once by resolve_presets().
+    #[clap(skip)]
+    preset_patterns: Vec<(String, String)>,
+
+    /// Read exclude patterns from the file, one pattern per line. Empty lines and lines starting with '#' are ignored.
+    /// Patterns in '.scrubignore' files found under the target directory are also honored. They are relative to the directory
+    /// where the '.scrubignore' file is located and take precedence over patterns of parent directories and command line.
+    #[clap(long, value_parser)]
+    exclude_from: Vec<String>,
+
+    /// Exclude directories or files whose path relative to the target directory matches the regular expression.
+    /// Paths of directories end with '/'.
+    #[clap(long, value_parser = Regex::new)]
+    exclude_regex: Vec<Regex>,
+
+    /// Exclude files larger than the size such as '50G'. Units K, M, G and T are powers of 1024.
+    #[clap(long, value_parser = rule::parse_size)]
+    exclude_larger_than: Option<u64>,
+
+    /// Exclude files modified within the duration such as '10m'. Units are s, m, h, d and w.
+    #[clap(long, value_parser = rule::parse_duration)]
+    exclude_newer_than: Option<Duration>,
+
+    /// Exclude entries of the type. Symbolic links are not followed.
+    /// These rules are applied even to paths re-included by '!' patterns.
+    #[clap(long, value_enum)]
+    exclude_type: Vec<FileKind>,
+
+    /// Skip directories that contain a valid 'CACHEDIR.TAG' file (https://bford.info/cachedir/).
+    #[clap(long, value_parser)]
+    exclude_caches: bool,
+
+    /// Skip directories that contain a file of the specified name such as '.nobackup'.
+    #[clap(long, value_parser)]
+    exclude_marker: Vec<String>,
+
+    /// Skip files that have the nodump attribute (chattr +d on Linux, chflags nodump on macOS).
+    #[clap(long, value_parser)]
+    exclude_nodump: bool,
+
+    /// Scrub only files that match the specified pattern. Patterns are the same as exclude patterns.
+    /// Exclude patterns take precedence over include patterns. Directories are always walked.
+    /// Include patterns are recorded in the control file and used in later runs unless other include patterns are specified.
+    /// Use '-I "*"' to scrub all files again.
+    #[clap(short = 'I', long, value_parser)]
+    include: Vec<String>,
+
+    /// Only list included and excluded paths with the rule that excluded each of them.
+    /// Files are not hashed and the control file is not changed.
+    #[clap(short = 'n', long, value_parser)]
+    dry_run: bool,
+
+    /// Show excluded paths and the ignore file and pattern that excluded each of them.
+    #[clap(long, value_parser)]
+    show_excluded: bool,
+
+    /// Also track permissions, owner and extended attributes of each file.
+    /// Files whose content is unchanged but whose metadata changed are reported separately.
+    /// Metadata is tracked in later runs since it is recorded in the control file.
+    #[clap(short = 'm', long, value_parser)]
+    track_metadata: bool,
+
+    /// Stop tracking metadata of a control file made with '-m'.
+    #[clap(long, value_parser, conflicts_with = "track-metadata")]
+    no_track_metadata: bool,
+
+    /// Format of the report. 'json' prints a document whose layout is described in README and versioned by 'schema_version'.
+    /// 'ndjson' prints events as one JSON object per line while the scan is going.
+    /// 'csv' and 'tsv' print one row per change and unreadable file for spreadsheets.
+    /// 'junit' and 'tap' print each file as a test case for CI systems. Content changes, removals, moves and unreadable files fail.
+    /// 'html' prints a self-contained document with a summary, trees of changes and sortable tables.
+    /// 'template' prints with the template file given by --template.
+    /// 'prom' prints metrics for the textfile collector of Prometheus node_exporter.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// Also write the report in the format to the file, given as FORMAT:PATH such as 'json:report.json'.
+    /// You can specify more than one. The report in --format is printed to standard output unless PATH of any is '-'.
+    #[clap(long, value_parser = output::parse_output_spec)]
+    output: Vec<OutputSpec>,
+
+    /// Template file for the format 'template' in the syntax of Jinja2. See README for the values available.
+    #[clap(long, value_parser)]
+    template: Option<String>,
+
+    /// Show changes of the text report grouped by the first DEPTH directories with counts and bytes of each kind.
+    /// Files are listed only for directories that have suspected corruption, removed or unreadable files.
+    #[clap(long, value_parser)]
+    rollup: Option<usize>,
+
+    /// Classes of outcome that make the exit code non-zero, separated by ','. The most severe one that occurred decides
+    /// the exit code: 3 for corruption, 4 for errors, 5 for removed, 6 for modified and 7 for added.
+    #[clap(long, value_enum, value_delimiter = ',', default_values_t = [Outcome::Corruption, Outcome::Errors, Outcome::Removed, Outcome::Modified])]
+    fail_on: Vec<Outcome>,
+}
+
+impl Cli {
+    /// Reads the preset file and resolves the names given by --preset into patterns.
+    pub fn resolve_presets(&mut self) -> io::Result<()> {
+        if ! self.preset.is_empty() {
+            self.preset_patterns = Presets::load(self.preset_file.as_deref())?.patterns(&self.preset)?;
+        }
+        Ok(())
+    }
+
+    /// Files this tool writes. They are excluded when located under the target directories.
+    /// Files that do not exist yet are resolved by their parent directory.
+    pub fn state_files(&self) -> Vec<PathBuf> {
+        let outputs = self.output.iter().filter(|o| ! o.is_stdout()).flat_map(|o| {
+            let path = PathBuf::from(&o.path);
+            let tmp = output::temp_path(&path);
+            [path, tmp]
+        });
+        std::iter::once(PathBuf::from(&self.control_file)).chain(outputs)
+            .filter(|f| ! f.as_os_str().is_empty())
+            .filter_map(|path| {
+                path.canonicalize().ok().or_else(|| {
+                    let parent = path.parent().filter(|p| ! p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+                    Some(parent.canonicalize().ok()?.join(path.file_name()?))
+                })
+            })
+            .collect()
+    }
 }
 
 fn main() {
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+    if let Err(err) = cli.resolve_presets() {
+        eprintln!("{}", err);
+        process::exit(exit_code::USAGE);
+    }
     let control_file = Path::new(&cli.control_file);
-    let target_dir = Path::new(&cli.target_dir);
-
-    let to = perform(&cli, &control_file, &target_dir, |report| {
-        println!("Summary:");
-        println!("  Added files: {}", report.added.len());
-        println!("  Removed files: {}", report.removed.len());
-        println!("  Modified files: {}", report.modified.len());
-
-        println!("");
-        println!("Details:");
-        println!("[Added files]");
-        for f in report.added.iter() {
-            println!("  {:?}", f);
-        }
+    let roots = Root::from_args(&cli.target_dir).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(exit_code::USAGE);
+    });
 
-        println!("[Removed files]");
-        for f in report.removed.iter() {
-            println!("  {:?}", f);
-        }
+    if cli.dry_run {
+        print_dry_run(&cli, &roots);
+        return;
+    }
 
-        println!("[Modified files]");
-        for f in report.modified.iter() {
-            println!("  {:?}", f);
-        }
+    let mut outputs: Vec<OutputSpec> = cli.output.clone();
+    if ! outputs.iter().any(|o| o.is_stdout()) {
+        outputs.insert(0, OutputSpec::stdout(cli.format));
+    }
+    // Collected at once so that the temporary files of outputs already opened are removed if one fails.
+    let renderers: Result<Vec<Box<dyn Renderer>>, String> = outputs.iter().map(|o| {
+        o.open(&cli).map_err(|err| format!("Cannot open '{}': {}", o.path, err))
+    }).collect();
+    let mut renderers = renderers.unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(exit_code::USAGE);
+    });
+
+    let (to, exit) = perform(&cli, control_file, &roots, &mut renderers);
+
+    if let Err(err) = to.save_to_file(control_file) {
+        eprintln!("Cannot write '{}': {}", control_file.display(), err);
+        process::exit(exit_code::FATAL);
+    }
+    if exit != exit_code::CLEAN {
+        process::exit(exit);
+    }
+}
+
+fn print_dry_run(cli: &Cli, roots: &[Root]) {
+    let cli = if Path::new(&cli.control_file).exists() {
+        with_recorded_settings(cli, &load_control_file(Path::new(&cli.control_file)).header)
+    } else {
+        cli.clone()
+    };
+    let dry_run = DryRun::walk(roots, &cli).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(exit_code::FATAL);
     });
 
-    to.save_to_file(&control_file).unwrap();
+    println!("Summary:");
+    println!("  Included files: {} ({} bytes)", dry_run.included_files, dry_run.included_bytes);
+    println!("  Excluded files: {} ({} bytes)", dry_run.excluded_files, dry_run.excluded_bytes);
+    println!("  Excluded directories: {}", dry_run.excluded_dirs);
+
+    println!();
+    println!("Details:");
+    println!("[Included]");
+    for f in dry_run.included.iter() {
+        println!("  {:?}", f);
+    }
+
+    println!("[Excluded]");
+    for e in dry_run.excluded.iter() {
+        println!("  {:?} by {}", e.path, e.rule);
+    }
+}
+
+/// Include patterns and tracking of metadata recorded in the control file are used unless specified in the command line.
+fn with_recorded_settings(cli: &Cli, header: &Header) -> Cli {
+    let mut cli = cli.clone();
+    if cli.include.is_empty() {
+        cli.include = header.include.clone();
+    }
+    if ! cli.no_track_metadata {
+        cli.track_metadata |= header.track_metadata;
+    }
+    cli
 }
 
-fn perform<F, P, O>(cli: &Cli, control_file: F, target_dir: P, out: O) -> ControlFile
-    where F: AsRef<Path>, P: AsRef<Path>, O: FnOnce(&Report)
+/// Scrubs the roots and writes events and the report through the renderer.
+/// Returns the new Controlfile and the exit code decided by the outcome.
+fn perform<F>(cli: &Cli, control_file: F, roots: &[Root], renderer: &mut dyn Renderer) -> (ControlFile, i32)
+    where F: AsRef<Path>
 {
-    let from = 
+    let started_at = SystemTime::now();
+    let mut from = 
         if ! control_file.as_ref().exists() {
             ControlFile::empty()
         } else {
-            ControlFile::load_from_file(&control_file).unwrap()
+            load_control_file(control_file.as_ref())
         };
 
-    let to = ControlFile::load_from_dir(&target_dir, &cli).unwrap();
-    
-    out(&Report::new(&from, &to));
+    let cli = &with_recorded_settings(cli, &from.header);
+    from.retain_included(roots, &Include::new(&cli.include));
+
+    let control_file = control_file.as_ref().to_string_lossy();
+    or_exit(renderer.event(&Event::ScanStarted {
+        version: env!("CARGO_PKG_VERSION"),
+        started_at: output::epoch_secs(started_at),
+        control_file: &control_file,
+        roots,
+    }));
+    // Content changes of existing files are reported as soon as they are hashed. Others need the whole tree.
+    let mut reported: HashSet<String> = HashSet::new();
+    let mut log = ScanLog::default();
+    let mut to = ControlFile::load_from_roots(roots, cli, &mut log, &mut |e| {
+        or_exit(renderer.event(e));
+        if let Event::FileHashed { path, entry, .. } = e {
+            if let Some(old) = from.get(path) {
+                if let Some(kind) = report::content_change(old, entry) {
+                    or_exit(renderer.event(&Event::FileChanged(Change::new(kind, path, path, Some(old), Some(entry)))));
+                    reported.insert(path.to_string());
+                }
+            }
+        }
+    }).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(exit_code::FATAL);
+    });
+    to.keep_unreadable(&from, &log.errors);
 
-    to
+    let report = Report::new(&from, &to);
+    for c in report.changes().into_iter() {
+        if c.old_path != c.path || ! reported.contains(c.path) {
+            or_exit(renderer.event(&Event::FileChanged(c)));
+        }
+    }
+    let summary = report.summary("");
+    let exit = exit_code::of(&cli.fail_on, &summary, log.errors.len());
+    or_exit(renderer.event(&Event::ScanFinished {
+        finished_at: output::epoch_secs(SystemTime::now()), summary, errors: log.errors.len(),
+    }));
+    or_exit(renderer.render(&report, &log, &Run::new(started_at, &control_file, roots, cli.track_metadata)));
+    for names in report.case_collisions.iter() {
+        eprintln!("Warning: names collide on case-insensitive file systems: {:?}", names);
+    }
+
+    (to, exit)
+}
+
+/// Aborts with FATAL if the Controlfile cannot be read.
+fn load_control_file(path: &Path) -> ControlFile {
+    ControlFile::load_from_file(path).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(exit_code::FATAL);
+    })
+}
+
+/// Aborts without updating the Controlfile if a report cannot be written.
+fn or_exit(result: io::Result<()>) {
+    if let Err(err) = result {
+        eprintln!("Cannot write report: {}", err);
+        process::exit(exit_code::FATAL);
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{fs::{File, self}, io::Write};
+    use std::{fs::{File, self}, io::{self, Write}};
     use tempfile::tempdir;
-    use crate::{perform, Cli};
+    use crate::{perform, Cli, output::{Renderer, Run}, report::Report, root::Root, scan_log::ScanLog};
+
+    /// Passes the report to the closure.
+    struct Check<F: FnMut(&Report)>(F);
+
+    impl<F: FnMut(&Report)> Renderer for Check<F> {
+        fn render(&mut self, report: &Report, _log: &ScanLog, _run: &Run) -> io::Result<()> {
+            (self.0)(report);
+            Ok(())
+        }
+    }
 
     #[test]
     fn tiny_case() {
@@ -106,20 +394,17 @@ mod tests {
         let from = ctrl_dir.path().join("Controlfile");
         let mut report_called = false;
 
-        let cli = Cli {
-            control_file: "".to_owned(),
-            exclude: vec![],
-            target_dir: "".to_owned(),
-        };
+        let cli = Cli::default();
         
-        let to = perform(&cli, &from, &tmp_dir, |report| {
+        let (to, _) = perform(&cli, &from, &[Root::new("", tmp_dir.path())], &mut Check(|report: &Report| {
             report_called = true;
             assert_eq!(report.added.len(), 3);
             assert_eq!(report.removed.len(), 0);
             assert_eq!(report.modified.len(), 0);
-        });
+            assert_eq!(report.added_dirs, vec!["foo/"]);
+        }));
 
-        assert_eq!(report_called, true);
+        assert!(report_called);
         to.save_to_file(&from).unwrap();
 
         {
@@ -133,12 +418,8 @@ mod tests {
         fs::remove_file(tmp_dir.path().join("foo/foo2.txt")).unwrap();
 
         report_called = false;
-        let cli = Cli {
-            control_file: "".to_owned(),
-            exclude: vec![],
-            target_dir: "".to_owned(),
-        };
-        perform(&cli, &from, &tmp_dir, |report| {
+        let cli = Cli::default();
+        perform(&cli, &from, &[Root::new("", tmp_dir.path())], &mut Check(|report: &Report| {
             report_called = true;
             assert_eq!(report.added.len(), 1);
             assert_eq!(report.added[0], "foo/foo3.txt");
@@ -148,8 +429,62 @@ mod tests {
 
             assert_eq!(report.modified.len(), 1);
             assert_eq!(report.modified[0], "foo/foo1.txt");
-        });
+        }));
 
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn include_is_kept_in_control_file() {
+        let tmp_dir = tempdir().unwrap();
+        let ctrl_dir = tempdir().unwrap();
+        fs::write(tmp_dir.path().join("a.raw"), b"012").unwrap();
+        fs::write(tmp_dir.path().join("b.jpg"), b"ABC").unwrap();
+        let from = ctrl_dir.path().join("Controlfile");
+        let roots = [Root::new("", tmp_dir.path())];
+
+        let (to, _) = perform(&Cli::default(), &from, &roots, &mut Check(|report: &Report| {
+            assert_eq!(report.added, vec!["a.raw", "b.jpg"]);
+        }));
+        to.save_to_file(&from).unwrap();
+
+        let cli = Cli {
+            include: vec!["*.raw".to_owned()],
+            ..Cli::default()
+        };
+        let (to, _) = perform(&cli, &from, &roots, &mut Check(|report: &Report| {
+            assert_eq!(report.added.len(), 0);
+            assert_eq!(report.removed.len(), 0);
+        }));
+        to.save_to_file(&from).unwrap();
+
+        fs::write(tmp_dir.path().join("c.jpg"), b"DEF").unwrap();
+        let (to, _) = perform(&Cli::default(), &from, &roots, &mut Check(|report: &Report| {
+            assert_eq!(report.added.len(), 0);
+            assert_eq!(report.removed.len(), 0);
+        }));
+        assert_eq!(to.files(), vec!["a.raw"]);
+    }
+
+    #[test]
+    fn metadata_tracking_is_kept_in_control_file() {
+        let tmp_dir = tempdir().unwrap();
+        let ctrl_dir = tempdir().unwrap();
+        fs::write(tmp_dir.path().join("a.txt"), b"012").unwrap();
+        let from = ctrl_dir.path().join("Controlfile");
+        let roots = [Root::new("", tmp_dir.path())];
+
+        let cli = Cli { track_metadata: true, ..Cli::default() };
+        let (to, _) = perform(&cli, &from, &roots, &mut Check(|_: &Report| {}));
+        to.save_to_file(&from).unwrap();
+
+        let (to, _) = perform(&Cli::default(), &from, &roots, &mut Check(|_: &Report| {}));
+        assert!(to.header.track_metadata);
+        assert!(to.get("a.txt").unwrap().metadata.is_some());
+        to.save_to_file(&from).unwrap();
+
+        let cli = Cli { no_track_metadata: true, ..Cli::default() };
+        let (to, _) = perform(&cli, &from, &roots, &mut Check(|_: &Report| {}));
+        assert!(! to.header.track_metadata);
+        assert!(to.get("a.txt").unwrap().metadata.is_none());
+    }
+}
@@ -0,0 +1,99 @@
+use std::{fmt, fs::File, io::Read, path::Path};
+
+/// Cache directory tag defined at https://bford.info/cachedir/
+pub const CACHEDIR_TAG: &str = "CACHEDIR.TAG";
+const CACHEDIR_TAG_SIGNATURE: &[u8] = b"Signature: 8a477f597d28d172789f06886806bc55";
+
+#[cfg(target_os = "linux")]
+const FS_NODUMP_FL: libc::c_long = 0x00000040;
+
+/// Why an entry was skipped without matching any exclude pattern or rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    /// Directory that contains a valid CACHEDIR.TAG.
+    CacheDirTag,
+    /// Directory that contains the marker file.
+    Marker(String),
+    /// File with the nodump attribute.
+    NoDump,
+    /// Control file or other file this tool writes.
+    StateFile,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::CacheDirTag => write!(f, "--exclude-caches ({})", CACHEDIR_TAG),
+            SkipReason::Marker(name) => write!(f, "--exclude-marker '{}'", name),
+            SkipReason::NoDump => write!(f, "--exclude-nodump"),
+            SkipReason::StateFile => write!(f, "state file of disk_scrub"),
+        }
+    }
+}
+
+/// True if the directory has CACHEDIR.TAG that starts with the signature.
+pub fn is_cache_dir(dir: &Path) -> bool {
+    let mut buf = [0u8; CACHEDIR_TAG_SIGNATURE.len()];
+    File::open(dir.join(CACHEDIR_TAG))
+        .and_then(|mut f| f.read_exact(&mut buf))
+        .map(|_| buf == CACHEDIR_TAG_SIGNATURE)
+        .unwrap_or(false)
+}
+
+/// True if the file has the nodump attribute (chattr +d on Linux, chflags nodump on macOS).
+/// File systems that do not support the attribute are regarded as not having it.
+#[cfg(target_os = "linux")]
+pub fn has_nodump(path: &Path) -> bool {
+    use std::{fs::OpenOptions, os::unix::{fs::OpenOptionsExt, io::AsRawFd}};
+
+    let file = match OpenOptions::new().read(true).custom_flags(libc::O_NONBLOCK).open(path) {
+        Ok(f) => f,
+        Err(_) => return false,
+    };
+    let mut flags: libc::c_long = 0;
+    let ret = unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_GETFLAGS, &mut flags) };
+    ret == 0 && flags & FS_NODUMP_FL != 0
+}
+
+#[cfg(target_os = "macos")]
+pub fn has_nodump(path: &Path) -> bool {
+    use std::os::macos::fs::MetadataExt;
+
+    std::fs::metadata(path).map(|m| m.st_flags() & libc::UF_NODUMP != 0).unwrap_or(false)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn has_nodump(_path: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use tempfile::tempdir;
+    use super::{is_cache_dir, has_nodump, CACHEDIR_TAG};
+
+    #[test]
+    fn can_detect_cache_dir() {
+        let tmp_dir = tempdir().unwrap();
+        assert!(!is_cache_dir(tmp_dir.path()));
+
+        fs::write(tmp_dir.path().join(CACHEDIR_TAG), b"Signature: 0000").unwrap();
+        assert!(!is_cache_dir(tmp_dir.path()));
+
+        fs::write(
+            tmp_dir.path().join(CACHEDIR_TAG),
+            b"Signature: 8a477f597d28d172789f06886806bc55\n# This file is a cache directory tag.\n"
+        ).unwrap();
+        assert!(is_cache_dir(tmp_dir.path()));
+    }
+
+    #[test]
+    fn plain_file_is_not_nodump() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("foo.txt");
+        File::create(&path).unwrap();
+        assert!(!has_nodump(&path));
+        assert!(!has_nodump(&tmp_dir.path().join("non_existent")));
+    }
+}
@@ -0,0 +1,171 @@
+use std::{fmt, fs, io::Error, os::unix::fs::MetadataExt, path::Path};
+
+use sha2::{Sha256, Digest};
+
+use crate::control_file::ParseError;
+
+/// Size and modification time of a file. Both usually change when the file is edited legitimately.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stat {
+    pub size: u64,
+    pub mtime_sec: i64,
+    pub mtime_nsec: i64,
+}
+
+impl Stat {
+    pub fn from_path(path: &Path) -> Result<Self, Error> {
+        let meta = fs::metadata(path)?;
+
+        Ok(Self { size: meta.len(), mtime_sec: meta.mtime(), mtime_nsec: meta.mtime_nsec() })
+    }
+
+    pub fn parse(size: &str, mtime: &str) -> Result<Self, ParseError> {
+        let invalid = || ParseError::InvalidStatFormat(format!("{}\t{}", size, mtime));
+        let size = size.parse::<u64>().map_err(|_| invalid())?;
+        let (sec, nsec) = mtime.split_once('.').ok_or_else(invalid)?;
+        let mtime_sec = sec.parse::<i64>().map_err(|_| invalid())?;
+        let mtime_nsec = nsec.parse::<i64>().map_err(|_| invalid())?;
+
+        Ok(Self { size, mtime_sec, mtime_nsec })
+    }
+
+    /// Modification time as seconds since the Unix epoch with nanoseconds such as '1700000000.000000001'.
+    pub fn mtime(&self) -> String {
+        format!("{}.{:09}", self.mtime_sec, self.mtime_nsec)
+    }
+}
+
+impl fmt::Display for Stat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\t{}", self.size, self.mtime())
+    }
+}
+
+/// Metadata of a file that is not reflected in its content hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    /// SHA-256 digest over all extended attributes (names and values).
+    pub xattr: Vec<u8>,
+}
+
+impl Metadata {
+    /// Follows symbolic links as the content hash does, so that every column describes the same file.
+    pub fn from_path(path: &Path) -> Result<Self, Error> {
+        let meta = fs::metadata(path)?;
+
+        Ok(
+            Self {
+                mode: meta.mode() & 0o7777, uid: meta.uid(), gid: meta.gid(), xattr: xattr_hash(path)?,
+            }
+        )
+    }
+
+    pub fn parse(mode: &str, uid: &str, gid: &str, xattr: &str) -> Result<Self, ParseError> {
+        let invalid = || ParseError::InvalidMetadataFormat(format!("{}\t{}\t{}\t{}", mode, uid, gid, xattr));
+        let mode = u32::from_str_radix(mode, 8).map_err(|_| invalid())?;
+        let uid = uid.parse::<u32>().map_err(|_| invalid())?;
+        let gid = gid.parse::<u32>().map_err(|_| invalid())?;
+        let xattr = hex::decode(xattr).map_err(|_| invalid())?;
+        if xattr.len() != 32 {
+            return Err(invalid());
+        }
+
+        Ok(Self { mode, uid, gid, xattr })
+    }
+}
+
+impl fmt::Display for Metadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:o}\t{}\t{}\t{}", self.mode, self.uid, self.gid, hex::encode(&self.xattr))
+    }
+}
+
+fn xattr_hash(path: &Path) -> Result<Vec<u8>, Error> {
+    let mut names: Vec<_> = xattr::list_deref(path)?.collect();
+    names.sort();
+    let mut hasher = Sha256::new();
+
+    for name in names.iter() {
+        hasher.update(name.as_encoded_bytes());
+        hasher.update([0u8]);
+        if let Some(value) = xattr::get_deref(path, name)? {
+            hasher.update(&value);
+        }
+        hasher.update([0u8]);
+    }
+
+    Ok(hasher.finalize().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File, Permissions};
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+    use crate::control_file::{ParseError, str_hash};
+    use super::{Metadata, Stat};
+
+    #[test]
+    fn can_read_mode() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("foo.txt");
+        File::create(&path).unwrap();
+        fs::set_permissions(&path, Permissions::from_mode(0o640)).unwrap();
+
+        let meta = Metadata::from_path(&path).unwrap();
+        assert_eq!(meta.mode, 0o640);
+
+        fs::set_permissions(&path, Permissions::from_mode(0o600)).unwrap();
+        assert_ne!(Metadata::from_path(&path).unwrap(), meta);
+    }
+
+    #[test]
+    fn follows_symlink() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("foo.txt");
+        File::create(&path).unwrap();
+        fs::set_permissions(&path, Permissions::from_mode(0o640)).unwrap();
+        let link = tmp_dir.path().join("link");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+        // Some file systems do not support user attributes. The mode is still compared then.
+        let _ = xattr::set(&path, "user.disk_scrub", b"1");
+
+        assert_eq!(Metadata::from_path(&link).unwrap(), Metadata::from_path(&path).unwrap());
+    }
+
+    #[test]
+    fn can_parse_and_format() {
+        let meta = Metadata { mode: 0o644, uid: 1000, gid: 100, xattr: str_hash("") };
+        let s = meta.to_string();
+        let cols: Vec<&str> = s.split('\t').collect();
+        assert_eq!(cols[0], "644");
+        assert_eq!(Metadata::parse(cols[0], cols[1], cols[2], cols[3]).unwrap(), meta);
+    }
+
+    #[test]
+    fn can_read_stat() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("foo.txt");
+        fs::write(&path, b"012").unwrap();
+
+        let stat = Stat::from_path(&path).unwrap();
+        assert_eq!(stat.size, 3);
+        let s = stat.to_string();
+        let (size, mtime) = s.split_once('\t').unwrap();
+        assert_eq!(Stat::parse(size, mtime).unwrap(), stat);
+
+        assert_eq!(Stat::parse("3", "1700000000.000000001").unwrap().to_string(), "3\t1700000000.000000001");
+        assert_eq!(Stat::parse("3", "1700000000").err().unwrap(), ParseError::InvalidStatFormat("3\t1700000000".to_owned()));
+    }
+
+    #[test]
+    fn invalid_format() {
+        assert_eq!(
+            Metadata::parse("9", "0", "0", "00").err().unwrap(),
+            ParseError::InvalidMetadataFormat("9\t0\t0\t00".to_owned())
+        );
+    }
+}
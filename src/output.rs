@@ -0,0 +1,256 @@
+use std::{fs::{self, File, OpenOptions}, io::{self, BufWriter, Write}, path::{Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{
+    ci_report::{JunitRenderer, TapRenderer}, csv_report::CsvRenderer, event::{Event, NdjsonRenderer}, html_report::HtmlRenderer, prom_report::PromRenderer,
+    json_report::JsonRenderer, report::Report, root::Root, scan_log::ScanLog, template_report::{Template, TemplateRenderer},
+    text_report::TextRenderer, Cli,
+};
+
+/// Format of the report printed after a scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    /// Events written as one JSON object per line while the scan is going.
+    Ndjson,
+    Csv,
+    Tsv,
+    /// JUnit XML for CI systems. Each file is a test case.
+    Junit,
+    /// Test Anything Protocol. Each file is a test point.
+    Tap,
+    /// Self-contained HTML document for people.
+    Html,
+    /// Layout given by '--template'.
+    Template,
+    /// Metrics for the textfile collector of Prometheus node_exporter.
+    Prom,
+}
+
+/// Writes a report in some format. Renderers receive events while the scan is going and the report after it.
+pub trait Renderer {
+    /// Most renderers only need the final report and ignore events.
+    fn event(&mut self, _event: &Event) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn render(&mut self, report: &Report, log: &ScanLog, run: &Run) -> io::Result<()>;
+}
+
+/// Runs renderers in order so that more than one output can be written in a run.
+impl Renderer for Vec<Box<dyn Renderer>> {
+    fn event(&mut self, event: &Event) -> io::Result<()> {
+        self.iter_mut().try_for_each(|r| r.event(event))
+    }
+
+    fn render(&mut self, report: &Report, log: &ScanLog, run: &Run) -> io::Result<()> {
+        self.iter_mut().try_for_each(|r| r.render(report, log, run))
+    }
+}
+
+/// Destination of a report given as 'FORMAT:PATH'. Path '-' means standard output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputSpec {
+    pub format: OutputFormat,
+    pub path: String,
+}
+
+impl OutputSpec {
+    pub fn stdout(format: OutputFormat) -> Self {
+        Self { format, path: "-".to_owned() }
+    }
+
+    pub fn is_stdout(&self) -> bool {
+        self.path == "-"
+    }
+
+    /// Returns the renderer that writes to the destination. A file is written to its temporary file and renamed when
+    /// the report is complete. The temporary file is created once here so that an unwritable destination is reported
+    /// before the scan.
+    pub fn open(&self, cli: &Cli) -> io::Result<Box<dyn Renderer>> {
+        if self.is_stdout() {
+            return self.renderer(|| Ok(Box::new(io::stdout())), cli);
+        }
+        let path = PathBuf::from(&self.path);
+        let tmp = temp_path(&path);
+        File::create(&tmp)?;
+        fs::remove_file(&tmp)?;
+        let out = LazyFile { path: tmp.clone(), file: None };
+        let renderer = self.renderer(|| Ok(Box::new(out)), cli)?;
+        Ok(Box::new(FileOutput { renderer, tmp, path, renamed: false }))
+    }
+
+    fn renderer<O>(&self, out: O, cli: &Cli) -> io::Result<Box<dyn Renderer>>
+        where O: FnOnce() -> io::Result<Box<dyn Write>>
+    {
+        Ok(
+            match self.format {
+                OutputFormat::Text => Box::new(TextRenderer::new(out()?, cli)),
+                OutputFormat::Json => Box::new(JsonRenderer(out()?)),
+                OutputFormat::Ndjson => Box::new(NdjsonRenderer(out()?)),
+                OutputFormat::Csv => Box::new(CsvRenderer(out()?, b',')),
+                OutputFormat::Tsv => Box::new(CsvRenderer(out()?, b'\t')),
+                OutputFormat::Junit => Box::new(JunitRenderer(out()?)),
+                OutputFormat::Tap => Box::new(TapRenderer(out()?)),
+                OutputFormat::Html => Box::new(HtmlRenderer(out()?)),
+                OutputFormat::Template => {
+                    let path = cli.template.as_deref().ok_or_else(|| io::Error::other("--template is required for the format 'template'."))?;
+                    let template = Template::load(path)?;
+                    Box::new(TemplateRenderer { out: out()?, template })
+                }
+                OutputFormat::Prom => Box::new(PromRenderer(out()?)),
+            }
+        )
+    }
+}
+
+/// Report file that is written to the temporary file and renamed to the destination when the report is complete,
+/// so that an aborted run leaves the previous report as it was.
+struct FileOutput {
+    renderer: Box<dyn Renderer>,
+    tmp: PathBuf,
+    path: PathBuf,
+    renamed: bool,
+}
+
+impl Renderer for FileOutput {
+    fn event(&mut self, event: &Event) -> io::Result<()> {
+        self.renderer.event(event)
+    }
+
+    fn render(&mut self, report: &Report, log: &ScanLog, run: &Run) -> io::Result<()> {
+        self.renderer.render(report, log, run)?;
+        OpenOptions::new().write(true).create(true).truncate(false).open(&self.tmp)?.sync_all()?;
+        fs::rename(&self.tmp, &self.path)?;
+        self.renamed = true;
+        Ok(())
+    }
+}
+
+impl Drop for FileOutput {
+    fn drop(&mut self) {
+        if ! self.renamed {
+            let _ = fs::remove_file(&self.tmp);
+        }
+    }
+}
+
+/// Creates the file on the first write so that nothing is left behind if the run aborts before the report is written.
+struct LazyFile {
+    path: PathBuf,
+    file: Option<BufWriter<File>>,
+}
+
+impl Write for LazyFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.file.is_none() {
+            self.file = Some(BufWriter::new(File::create(&self.path)?));
+        }
+        self.file.as_mut().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.file.as_mut() {
+            Some(f) => f.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Hidden file in the same directory as the destination so that it is on the same file system and ignored by tools
+/// that read files by the extension. The name is fixed so that a leftover one is excluded from the scan like the output itself.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    path.with_file_name(format!(".{}.tmp", name))
+}
+
+/// Parses 'FORMAT:PATH' of '--output'. Only the first ':' separates them so that the path may contain ':'.
+pub fn parse_output_spec(s: &str) -> Result<OutputSpec, String> {
+    let (format, path) = s.split_once(':').ok_or_else(|| format!("Expected FORMAT:PATH but got '{}'.", s))?;
+    let format = OutputFormat::from_str(format, true)?;
+    if path.is_empty() {
+        return Err(format!("Path is empty in '{}'. Use '-' for standard output.", s));
+    }
+
+    Ok(OutputSpec { format, path: path.to_owned() })
+}
+
+/// Information about a run that is not part of the report itself.
+#[derive(Debug, Serialize)]
+pub struct Run {
+    pub version: &'static str,
+    /// Seconds since the Unix epoch.
+    pub started_at: u64,
+    /// Seconds since the Unix epoch.
+    pub finished_at: u64,
+    pub control_file: String,
+    pub roots: Vec<Root>,
+    pub track_metadata: bool,
+}
+
+impl Run {
+    pub fn new(started_at: SystemTime, control_file: &str, roots: &[Root], track_metadata: bool) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            started_at: epoch_secs(started_at),
+            finished_at: epoch_secs(SystemTime::now()),
+            control_file: control_file.to_owned(),
+            roots: roots.to_vec(),
+            track_metadata,
+        }
+    }
+}
+
+pub fn epoch_secs(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::SystemTime};
+    use tempfile::tempdir;
+    use crate::{control_file::ControlFile, event::Event, report::Report, root::Root, scan_log::ScanLog, Cli};
+    use super::{parse_output_spec, OutputFormat, OutputSpec, Run};
+
+    #[test]
+    fn can_parse_output_spec() {
+        assert_eq!(parse_output_spec("json:/tmp/a:b.json").unwrap(), OutputSpec { format: OutputFormat::Json, path: "/tmp/a:b.json".to_owned() });
+        assert_eq!(parse_output_spec("TEXT:-").unwrap(), OutputSpec::stdout(OutputFormat::Text));
+        assert!(parse_output_spec("json").is_err());
+        assert!(parse_output_spec("json:").is_err());
+        assert!(parse_output_spec("xml:a").is_err());
+    }
+
+    #[test]
+    fn file_is_replaced_only_when_report_is_complete() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("report.json");
+        fs::write(&path, "previous").unwrap();
+        let events = OutputSpec { format: OutputFormat::Ndjson, path: path.to_string_lossy().to_string() };
+        let mut renderer = events.open(&Cli::default()).unwrap();
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        renderer.event(&Event::Error { path: "a", message: "denied" }).unwrap();
+        renderer.event(&Event::Error { path: "b", message: "denied" }).unwrap();
+        assert!(dir.path().join(".report.json.tmp").exists());
+        drop(renderer);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "previous");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let spec = OutputSpec { format: OutputFormat::Json, path: path.to_string_lossy().to_string() };
+
+        let (from, to) = (ControlFile::empty(), ControlFile::empty());
+        let run = Run::new(SystemTime::now(), "Controlfile", &[Root::new("", "/tmp")], false);
+        spec.open(&Cli::default()).unwrap().render(&Report::new(&from, &to), &ScanLog::default(), &run).unwrap();
+        assert!(fs::read_to_string(&path).unwrap().starts_with('{'));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        for format in [OutputFormat::Json, OutputFormat::Prom] {
+            let missing = OutputSpec { format, path: dir.path().join("no/report").to_string_lossy().to_string() };
+            assert!(missing.open(&Cli::default()).is_err());
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::{collections::HashMap, env, fs, io::Error, path::PathBuf};
+
+/// Built-in exclude presets selected by '--preset'.
+const BUILTIN: &[(&str, &[&str])] = &[
+    ("macos", &[
+        ".DS_Store", "._*", ".AppleDouble/", ".Spotlight-V100/", ".Trashes/", ".fseventsd/",
+        ".TemporaryItems/", ".DocumentRevisions-V100/",
+    ]),
+    ("windows", &["Thumbs.db", "ehthumbs.db", "desktop.ini", "$RECYCLE.BIN/", "System Volume Information/"]),
+    ("editors", &["*~", ".*.swp", ".*.swo", "#*#", ".#*"]),
+    ("vcs", &[".git/", ".svn/", ".hg/", ".bzr/", "CVS/"]),
+    ("downloads", &["*.crdownload", "*.part", "*.partial", "*.download", "*.opdownload"]),
+];
+
+/// Location of user-defined presets when '--preset-file' is not specified.
+fn default_preset_file() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+    Some(config_dir.join("disk_scrub").join("presets"))
+}
+
+/// Patterns of each preset name. Each pattern has its origin for reporting.
+pub struct Presets {
+    presets: HashMap<String, Vec<(String, String)>>,
+}
+
+impl Presets {
+    pub fn builtin() -> Self {
+        let presets = BUILTIN.iter().map(|(name, patterns)| {
+            let origin = format!("--preset {}", name);
+            (name.to_string(), patterns.iter().map(|p| (p.to_string(), origin.clone())).collect())
+        }).collect();
+
+        Self { presets }
+    }
+
+    /// Built-in presets and presets in the file. Presets in the file override built-in presets of the same name.
+    /// The default file is read only if it exists.
+    pub fn load(preset_file: Option<&str>) -> Result<Self, Error> {
+        let mut ret = Self::builtin();
+        let path = match preset_file {
+            Some(f) => Some(PathBuf::from(f)),
+            None => default_preset_file().filter(|p| p.exists()),
+        };
+        if let Some(path) = path {
+            ret.read_file(&path)?;
+        }
+        Ok(ret)
+    }
+
+    /// Reads sections such as '[name]' followed by patterns. Empty lines and lines starting with '#' are ignored.
+    fn read_file(&mut self, path: &PathBuf) -> Result<(), Error> {
+        let mut current: Option<String> = None;
+        for (idx, l) in fs::read_to_string(path)?.lines().enumerate() {
+            let l = l.trim_end();
+            if l.is_empty() || l.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = l.strip_prefix('[').and_then(|n| n.strip_suffix(']')) {
+                self.presets.insert(name.to_owned(), vec![]);
+                current = Some(name.to_owned());
+                continue;
+            }
+            match &current {
+                Some(name) => self.presets.get_mut(name).unwrap().push(
+                    (l.to_owned(), format!("{}:{}", path.to_string_lossy(), idx + 1))
+                ),
+                None => return Err(Error::other(
+                    format!("{}:{}: Pattern '{}' is not in any preset section.", path.to_string_lossy(), idx + 1, l)
+                )),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn patterns(&self, names: &[String]) -> Result<Vec<(String, String)>, Error> {
+        let mut ret: Vec<(String, String)> = vec![];
+        for n in names.iter() {
+            match self.presets.get(n) {
+                Some(patterns) => ret.extend(patterns.iter().cloned()),
+                None => {
+                    let mut known: Vec<&String> = self.presets.keys().collect();
+                    known.sort();
+                    return Err(Error::other(format!("Unknown preset '{}'. Available presets: {:?}.", n, known)));
+                }
+            }
+        }
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::tempdir;
+    use super::Presets;
+
+    #[test]
+    fn builtin_presets() {
+        let presets = Presets::builtin();
+        let patterns = presets.patterns(&["macos".to_owned(), "editors".to_owned()]).unwrap();
+        assert!(patterns.contains(&(".DS_Store".to_owned(), "--preset macos".to_owned())));
+        assert!(patterns.contains(&("*~".to_owned(), "--preset editors".to_owned())));
+        assert!(presets.patterns(&["unknown".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn can_read_preset_file() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("presets");
+        fs::write(&path, "# comment\n[photo]\n*.xmp\n\n[vcs]\n.git/\n").unwrap();
+
+        let presets = Presets::load(Some(&path.to_string_lossy())).unwrap();
+        let origin = path.to_string_lossy();
+        assert_eq!(presets.patterns(&["photo".to_owned()]).unwrap(), vec![("*.xmp".to_owned(), format!("{}:3", origin))]);
+        assert_eq!(presets.patterns(&["vcs".to_owned()]).unwrap(), vec![(".git/".to_owned(), format!("{}:6", origin))]);
+
+        fs::write(&path, "*.xmp\n").unwrap();
+        assert!(Presets::load(Some(&path.to_string_lossy())).is_err());
+    }
+}
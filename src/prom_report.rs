@@ -0,0 +1,102 @@
+use std::io::{self, Write};
+
+use crate::{output::{Renderer, Run}, report::Report, root::Root, scan_log::ScanLog};
+
+/// Metrics in the text format of Prometheus for the textfile collector of node_exporter. Every metric is labelled by
+/// the root. Since the file is only written when a scan completes, the timestamp tells when the last successful run finished.
+pub fn write<W: Write>(w: &mut W, report: &Report, log: &ScanLog, run: &Run) -> io::Result<()> {
+    let label = |r: &Root| {
+        let name = if r.name.is_empty() { r.path.to_string_lossy().to_string() } else { r.name.clone() };
+        format!("root=\"{}\"", escape(&name))
+    };
+    let scans: Vec<(String, &Root)> = run.roots.iter().map(|r| (label(r), r)).collect();
+    let scan_of = |r: &Root| log.roots.iter().find(|s| s.name == r.name);
+
+    let mut metric = |name: &str, kind: &str, help: &str, values: &mut dyn Iterator<Item = (String, String)>| -> io::Result<()> {
+        writeln!(w, "# HELP disk_scrub_{} {}", name, help)?;
+        writeln!(w, "# TYPE disk_scrub_{} {}", name, kind)?;
+        for (labels, value) in values {
+            writeln!(w, "disk_scrub_{}{{{}}} {}", name, labels, value)?;
+        }
+        Ok(())
+    };
+
+    metric("files_scanned", "gauge", "Number of files hashed.", &mut scans.iter().map(|(l, r)| {
+        (l.clone(), scan_of(r).map(|s| s.files).unwrap_or(0).to_string())
+    }))?;
+    metric("bytes_scanned", "gauge", "Total size of files hashed.", &mut scans.iter().map(|(l, r)| {
+        (l.clone(), scan_of(r).map(|s| s.bytes).unwrap_or(0).to_string())
+    }))?;
+    metric("changes", "gauge", "Number of changes by category.", &mut scans.iter().flat_map(|(l, r)| {
+        let s = report.summary(&r.prefix());
+        [
+            ("added", s.added), ("removed", s.removed), ("modified", s.modified), ("corrupted", s.corrupted),
+            ("metadata_changed", s.metadata_changed), ("added_dirs", s.added_dirs), ("removed_dirs", s.removed_dirs),
+            ("normalization_matches", s.normalization_matches), ("moved", s.moved),
+        ].into_iter().map(move |(category, count)| (format!("{},category=\"{}\"", l, category), count.to_string()))
+    }))?;
+    metric("errors", "gauge", "Number of files that could not be read.", &mut scans.iter().map(|(l, r)| {
+        (l.clone(), log.errors.iter().filter(|e| e.path.starts_with(&r.prefix())).count().to_string())
+    }))?;
+    metric("duration_seconds", "gauge", "Time taken to scan the root.", &mut scans.iter().map(|(l, r)| {
+        (l.clone(), scan_of(r).map(|s| s.seconds).unwrap_or(0.0).to_string())
+    }))?;
+    metric("read_bytes_per_second", "gauge", "Bytes hashed per second.", &mut scans.iter().map(|(l, r)| {
+        let rate = scan_of(r).filter(|s| 0.0 < s.seconds).map(|s| s.bytes as f64 / s.seconds).unwrap_or(0.0);
+        (l.clone(), rate.to_string())
+    }))?;
+    metric("last_success_timestamp_seconds", "gauge", "Time when the last successful run finished.", &mut scans.iter().map(|(l, _)| {
+        (l.clone(), run.finished_at.to_string())
+    }))
+}
+
+/// Escapes a label value as the text format requires.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Writes the metrics. Files are replaced atomically by the output so that the collector never reads a partial file.
+pub struct PromRenderer<W: Write>(pub W);
+
+impl<W: Write> Renderer for PromRenderer<W> {
+    fn render(&mut self, report: &Report, log: &ScanLog, run: &Run) -> io::Result<()> {
+        write(&mut self.0, report, log, run)?;
+        self.0.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+    use crate::control_file::{ControlFile, Header, entry};
+    use crate::{output::{Renderer, Run}, report::Report, root::Root, scan_log::{RootScan, ScanLog, ScanError}};
+    use super::PromRenderer;
+
+    #[test]
+    fn can_write_prom_file() {
+        let from = ControlFile { header: Header::default(), entries: vec![entry("a/x", "a/x", None)] };
+        let to = ControlFile { header: Header::default(), entries: vec![entry("a/x", "a/x", None), entry("a/y", "a/y", None), entry("b\"/z", "b\"/z", None)] };
+        let report = Report::new(&from, &to);
+        let roots = [Root::new("a", "/mnt/a"), Root::new("b\"", "/mnt/b")];
+        let log = ScanLog {
+            errors: vec![ScanError { path: "a/w".to_owned(), message: "denied".to_owned() }],
+            roots: vec![
+                RootScan { name: "a".to_owned(), files: 2, bytes: 100, seconds: 2.0 },
+                RootScan { name: "b\"".to_owned(), files: 1, bytes: 0, seconds: 0.0 },
+            ],
+            ..ScanLog::default()
+        };
+        let run = Run::new(SystemTime::now(), "Controlfile", &roots, false);
+
+        let mut buf = vec![];
+        PromRenderer(&mut buf).render(&report, &log, &run).unwrap();
+        let prom = String::from_utf8(buf).unwrap();
+        assert!(prom.contains("# TYPE disk_scrub_files_scanned gauge\n"));
+        assert!(prom.contains("disk_scrub_files_scanned{root=\"a\"} 2\n"));
+        assert!(prom.contains("disk_scrub_changes{root=\"a\",category=\"added\"} 1\n"));
+        assert!(prom.contains("disk_scrub_changes{root=\"b\\\"\",category=\"added\"} 1\n"));
+        assert!(prom.contains("disk_scrub_errors{root=\"a\"} 1\n"));
+        assert!(prom.contains("disk_scrub_read_bytes_per_second{root=\"a\"} 50\n"));
+        assert!(prom.contains(&format!("disk_scrub_last_success_timestamp_seconds{{root=\"a\"}} {}\n", run.finished_at)));
+    }
+}
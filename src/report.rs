@@ -1,9 +1,75 @@
-use crate::control_file::ControlFile;
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::{control_file::{ControlFile, ControlFileEntry}, unicode};
 
 pub struct Report<'a> {
+    pub from: &'a ControlFile,
+    pub to: &'a ControlFile,
     pub added: Vec<&'a str>,
     pub removed: Vec<&'a str>,
+    /// Files whose content changed along with their size or modification time. Usually intended edits.
     pub modified: Vec<&'a str>,
+    /// Files whose content changed while their size and modification time did not. Likely silent corruption.
+    pub corrupted: Vec<&'a str>,
+    /// Files whose content is unchanged but whose permissions, owner or extended attributes changed.
+    pub metadata_changed: Vec<&'a str>,
+    pub added_dirs: Vec<&'a str>,
+    pub removed_dirs: Vec<&'a str>,
+    /// Removed and added paths (old, new) that are equal under Unicode normalization or case folding.
+    pub normalization_matches: Vec<(&'a str, &'a str)>,
+    /// Paths in the same directory whose names collide on a case-insensitive file system.
+    pub case_collisions: Vec<Vec<&'a str>>,
+    /// Removed and added files (old, new) with the same content.
+    pub moved: Vec<(&'a str, &'a str)>,
+}
+
+/// One changed path. Hashes are hex encoded and None for directories or when the path does not exist on that side.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Change<'a> {
+    pub kind: &'static str,
+    pub path: &'a str,
+    /// Differs from the path only for files paired by normalization, case or move.
+    pub old_path: &'a str,
+    pub old_hash: Option<String>,
+    pub new_hash: Option<String>,
+}
+
+pub const MODIFIED: &str = "modified";
+pub const CORRUPTED: &str = "corrupted";
+
+impl<'a> Change<'a> {
+    pub fn new(kind: &'static str, old_path: &'a str, path: &'a str, old: Option<&ControlFileEntry>, new: Option<&ControlFileEntry>) -> Self {
+        let hash = |e: Option<&ControlFileEntry>| e.filter(|e| ! e.is_dir()).map(|e| hex::encode(&e.sha256));
+        Self { kind, path, old_path, old_hash: hash(old), new_hash: hash(new) }
+    }
+}
+
+/// Classifies the change of content of the same file. A content change without a change of size and modification
+/// time is suspected corruption. Returns None if the content is unchanged.
+pub fn content_change(old: &ControlFileEntry, new: &ControlFileEntry) -> Option<&'static str> {
+    if old.sha256 == new.sha256 {
+        None
+    } else if old.stat.is_some() && old.stat == new.stat {
+        Some(CORRUPTED)
+    } else {
+        Some(MODIFIED)
+    }
+}
+
+/// Number of changes in each category.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Summary {
+    pub added: usize,
+    pub removed: usize,
+    pub modified: usize,
+    pub corrupted: usize,
+    pub metadata_changed: usize,
+    pub added_dirs: usize,
+    pub removed_dirs: usize,
+    pub normalization_matches: usize,
+    pub moved: usize,
 }
 
 impl<'a> Report<'a> {
@@ -11,6 +77,14 @@ impl<'a> Report<'a> {
         let mut added: Vec<&'a str> = vec![];
         let mut deleted: Vec<&'a str> = vec![];
         let mut modified: Vec<&'a str> = vec![];
+        let mut corrupted: Vec<&'a str> = vec![];
+        let mut metadata_changed: Vec<&'a str> = vec![];
+        let mut added_dirs: Vec<&'a str> = vec![];
+        let mut deleted_dirs: Vec<&'a str> = vec![];
+
+        fn push<'a>(e: &'a ControlFileEntry, files: &mut Vec<&'a str>, dirs: &mut Vec<&'a str>) {
+            if e.is_dir() { dirs.push(&e.file_path) } else { files.push(&e.file_path) }
+        }
 
         let mut from_idx = 0;
         let mut to_idx = 0;
@@ -19,26 +93,30 @@ impl<'a> Report<'a> {
             if from.len() <= from_idx && to.len() <= to_idx { break; }
             else if from.len() <= from_idx && to_idx < to.len() {
                 for i in to_idx..to.len() {
-                    added.push(&to[i].file_path);
+                    push(&to[i], &mut added, &mut added_dirs);
                 }
                 break;
             } else if from_idx < from.len() && to.len() <= to_idx {
                 for i in from_idx..from.len() {
-                    deleted.push(&from[i].file_path);
+                    push(&from[i], &mut deleted, &mut deleted_dirs);
                 }
                 break;
             } else {
                 let fc = &from[from_idx];
                 let tc = &to[to_idx];
                 if fc.file_path < tc.file_path {
-                    deleted.push(&fc.file_path);
+                    push(fc, &mut deleted, &mut deleted_dirs);
                     from_idx += 1;
                 } else if tc.file_path < fc.file_path {
-                    added.push(&tc.file_path);
+                    push(tc, &mut added, &mut added_dirs);
                     to_idx += 1;
                 } else {
-                    if tc.sha256 != fc.sha256 {
-                        modified.push(&tc.file_path);
+                    if let Some(kind) = content_change(fc, tc) {
+                        if kind == CORRUPTED { corrupted.push(&tc.file_path) } else { modified.push(&tc.file_path) }
+                    } else if let (Some(fm), Some(tm)) = (&fc.metadata, &tc.metadata) {
+                        if fm != tm {
+                            metadata_changed.push(&tc.file_path);
+                        }
                     }
                     from_idx += 1;
                     to_idx += 1;
@@ -46,47 +124,175 @@ impl<'a> Report<'a> {
             }
         }
 
-        Self { added, removed: deleted, modified }
+        let mut normalization_matches = unicode::pair_equivalent(&mut deleted, &mut added);
+        for (old, new) in normalization_matches.iter() {
+            if let (Some(fc), Some(tc)) = (from.get(old), to.get(new)) {
+                match content_change(fc, tc) {
+                    Some(CORRUPTED) => corrupted.push(new),
+                    Some(_) => modified.push(new),
+                    None => {}
+                }
+            }
+        }
+        modified.sort();
+        corrupted.sort();
+        normalization_matches.append(&mut unicode::pair_equivalent(&mut deleted_dirs, &mut added_dirs));
+        let moved = pair_moves(from, to, &mut deleted, &mut added);
+        let case_collisions = unicode::case_collisions(to.entries.iter().map(|e| e.file_path.as_str()));
+
+        Self {
+            from, to, added, removed: deleted, modified, corrupted, metadata_changed, added_dirs, removed_dirs: deleted_dirs,
+            normalization_matches, case_collisions, moved,
+        }
+    }
+
+    /// Path of the file in the previous run. Differs from the path only for files paired by normalization or case.
+    pub fn old_path_of(&self, path: &'a str) -> &'a str {
+        self.normalization_matches.iter().find(|(_, new)| *new == path).map(|(old, _)| *old).unwrap_or(path)
+    }
+
+    /// All changes in the order of added, removed, modified, corrupted, metadata changed, added and removed
+    /// directories, normalization matches and moves.
+    pub fn changes(&self) -> Vec<Change<'a>> {
+        let change = |kind, old_path: &'a str, path: &'a str| {
+            Change::new(kind, old_path, path, self.from.get(old_path), self.to.get(path))
+        };
+
+        let mut ret = vec![];
+        let groups: [(&'static str, &Vec<&'a str>); 7] = [
+            ("added", &self.added),
+            ("removed", &self.removed),
+            (MODIFIED, &self.modified),
+            (CORRUPTED, &self.corrupted),
+            ("metadata_changed", &self.metadata_changed),
+            ("added_dir", &self.added_dirs),
+            ("removed_dir", &self.removed_dirs),
+        ];
+        for (kind, paths) in groups {
+            for p in paths.iter() {
+                ret.push(change(kind, self.old_path_of(p), p));
+            }
+        }
+        for (old, new) in self.normalization_matches.iter() {
+            ret.push(change("normalization_match", old, new));
+        }
+        for (old, new) in self.moved.iter() {
+            ret.push(change("moved", old, new));
+        }
+        ret
+    }
+
+    /// Counts changes of paths that start with the prefix. Use empty prefix to count all.
+    pub fn summary(&self, prefix: &str) -> Summary {
+        let count = |paths: &Vec<&str>| paths.iter().filter(|p| p.starts_with(prefix)).count();
+
+        Summary {
+            added: count(&self.added),
+            removed: count(&self.removed),
+            modified: count(&self.modified),
+            corrupted: count(&self.corrupted),
+            metadata_changed: count(&self.metadata_changed),
+            added_dirs: count(&self.added_dirs),
+            removed_dirs: count(&self.removed_dirs),
+            normalization_matches: self.normalization_matches.iter().filter(|(_, new)| new.starts_with(prefix)).count(),
+            moved: self.moved.iter().filter(|(_, new)| new.starts_with(prefix)).count(),
+        }
     }
 }
 
+/// Removes pairs of removed/added files that have the same hash from both lists and returns them as (removed, added).
+/// When several files share the hash, files with the same name are paired first. The rest are paired only if
+/// exactly one removed and one added file remain. Otherwise they are left as removed/added since the pairing is ambiguous.
+fn pair_moves<'a>(
+    from: &'a ControlFile, to: &'a ControlFile, removed: &mut Vec<&'a str>, added: &mut Vec<&'a str>
+) -> Vec<(&'a str, &'a str)> {
+    let mut groups: HashMap<&'a [u8], (Vec<&'a str>, Vec<&'a str>)> = HashMap::new();
+    for r in removed.iter() {
+        groups.entry(&from.get(r).unwrap().sha256).or_default().0.push(r);
+    }
+    for a in added.iter() {
+        if let Some(g) = groups.get_mut(to.get(a).unwrap().sha256.as_slice()) {
+            g.1.push(a);
+        }
+    }
+
+    let file_name = |p: &str| p.rsplit('/').next().unwrap_or(p).to_owned();
+    let mut moved: Vec<(&'a str, &'a str)> = vec![];
+    for (_, (mut rs, mut adds)) in groups.into_iter() {
+        rs.retain(|r| {
+            match adds.iter().position(|a| file_name(a) == file_name(r)) {
+                Some(idx) => {
+                    moved.push((r, adds.remove(idx)));
+                    false
+                }
+                None => true,
+            }
+        });
+        if rs.len() == 1 && adds.len() == 1 {
+            moved.push((rs[0], adds[0]));
+        }
+    }
+    moved.sort();
+
+    let olds: HashSet<&str> = moved.iter().map(|(old, _)| *old).collect();
+    let news: HashSet<&str> = moved.iter().map(|(_, new)| *new).collect();
+    removed.retain(|r| ! olds.contains(r));
+    added.retain(|a| ! news.contains(a));
+    moved
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::control_file::{ControlFile, ControlFileEntry, str_hash};
-    use super::Report;
+    use crate::control_file::{ControlFile, ControlFileEntry, Header, entry, str_hash};
+    use crate::metadata::Metadata;
+    use super::{Report, Summary, CORRUPTED};
 
     #[test]
     fn can_create_report() {
         let from = ControlFile {
+            header: Header::default(),
             entries: vec![
                 ControlFileEntry {
                     file_path: "ABC".to_owned(),
                     sha256: str_hash("ABC"),
+                    stat: None,
+                    metadata: None,
                 },
                 ControlFileEntry {
                     file_path: "DEF".to_owned(),
                     sha256: str_hash("DEF"),
+                    stat: None,
+                    metadata: None,
                 },
                 ControlFileEntry {
                     file_path: "EFG".to_owned(),
                     sha256: str_hash("EFG"),
+                    stat: None,
+                    metadata: None,
                 },
             ]
         };
 
         let to = ControlFile {
+            header: Header::default(),
             entries: vec![
                 ControlFileEntry {
                     file_path: "DEF".to_owned(),
                     sha256: str_hash("DEF0"),
+                    stat: None,
+                    metadata: None,
                 },
                 ControlFileEntry {
                     file_path: "EFG".to_owned(),
                     sha256: str_hash("EFG"),
+                    stat: None,
+                    metadata: None,
                 },
                 ControlFileEntry {
                     file_path: "XYZ".to_owned(),
                     sha256: str_hash("XYZ"),
+                    stat: None,
+                    metadata: None,
                 },
             ]
         };
@@ -100,5 +306,287 @@ mod tests {
 
         assert_eq!(report.modified.len(), 1);
         assert_eq!(report.modified[0], "DEF");
+
+        assert_eq!(report.metadata_changed.len(), 0);
+    }
+
+    #[test]
+    fn can_summarize_by_prefix() {
+        let from = ControlFile {
+            header: Header::default(),
+            entries: vec![
+                ControlFileEntry {
+                    file_path: "a/ABC".to_owned(),
+                    sha256: str_hash("ABC"),
+                    stat: None,
+                    metadata: None,
+                },
+                ControlFileEntry {
+                    file_path: "b/ABC".to_owned(),
+                    sha256: str_hash("ABC"),
+                    stat: None,
+                    metadata: None,
+                },
+            ]
+        };
+
+        let to = ControlFile {
+            header: Header::default(),
+            entries: vec![
+                ControlFileEntry {
+                    file_path: "a/ABC".to_owned(),
+                    sha256: str_hash("ABC0"),
+                    stat: None,
+                    metadata: None,
+                },
+                ControlFileEntry {
+                    file_path: "a/DEF".to_owned(),
+                    sha256: str_hash("DEF"),
+                    stat: None,
+                    metadata: None,
+                },
+            ]
+        };
+
+        let report = Report::new(&from, &to);
+        assert_eq!(report.summary("a/"), Summary { added: 1, modified: 1, ..Summary::default() });
+        assert_eq!(report.summary("b/"), Summary { removed: 1, ..Summary::default() });
+        assert_eq!(report.summary(""), Summary { added: 1, removed: 1, modified: 1, ..Summary::default() });
+    }
+
+    #[test]
+    fn can_report_normalization_matches() {
+        let from = ControlFile {
+            header: Header::default(),
+            entries: vec![
+                ControlFileEntry {
+                    file_path: "caf\u{e9}.txt".to_owned(),
+                    sha256: str_hash("ABC"),
+                    stat: None,
+                    metadata: None,
+                },
+            ]
+        };
+
+        let to = ControlFile {
+            header: Header::default(),
+            entries: vec![
+                ControlFileEntry {
+                    file_path: "Foo".to_owned(),
+                    sha256: str_hash("DEF"),
+                    stat: None,
+                    metadata: None,
+                },
+                ControlFileEntry {
+                    file_path: "cafe\u{301}.txt".to_owned(),
+                    sha256: str_hash("ABC"),
+                    stat: None,
+                    metadata: None,
+                },
+                ControlFileEntry {
+                    file_path: "foo".to_owned(),
+                    sha256: str_hash("DEF"),
+                    stat: None,
+                    metadata: None,
+                },
+            ]
+        };
+
+        let report = Report::new(&from, &to);
+        assert_eq!(report.normalization_matches, vec![("caf\u{e9}.txt", "cafe\u{301}.txt")]);
+        assert_eq!(report.added, vec!["Foo", "foo"]);
+        assert_eq!(report.removed.len(), 0);
+        assert_eq!(report.modified.len(), 0);
+        assert_eq!(report.case_collisions, vec![vec!["Foo", "foo"]]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn normalization_matches_can_be_corrupted() {
+        let from = ControlFile {
+            header: Header::default(),
+            entries: vec![
+                entry("b.txt", "ABC", Some(1)),
+                entry("caf\u{e9}.txt", "ABC", Some(1)),
+                entry("d.txt", "ABC", Some(1)),
+                entry("na\u{ef}ve.txt", "ABC", Some(1)),
+            ]
+        };
+        let to = ControlFile {
+            header: Header::default(),
+            entries: vec![
+                entry("b.txt", "XYZ", Some(2)),
+                entry("cafe\u{301}.txt", "XYZ", Some(1)),
+                entry("d.txt", "XYZ", Some(1)),
+                entry("nai\u{308}ve.txt", "XYZ", Some(2)),
+            ]
+        };
+
+        let report = Report::new(&from, &to);
+        assert_eq!(report.corrupted, vec!["cafe\u{301}.txt", "d.txt"]);
+        assert_eq!(report.modified, vec!["b.txt", "nai\u{308}ve.txt"]);
+        assert_eq!(report.changes().iter().find(|c| c.path == "cafe\u{301}.txt" && c.kind == CORRUPTED).unwrap().old_path, "caf\u{e9}.txt");
+    }
+
+    #[test]
+    fn can_report_moves() {
+        let from = ControlFile {
+            header: Header::default(),
+            entries: vec![
+                entry("a/dup.txt", "DUP", None),
+                entry("a/photo.jpg", "JPG", None),
+                entry("b/dup.txt", "DUP", None),
+                entry("c/x.bin", "BIN", None),
+                entry("c/y.bin", "BIN", None),
+                entry("lost.txt", "LOST", None),
+            ]
+        };
+        let to = ControlFile {
+            header: Header::default(),
+            entries: vec![
+                entry("d/dup.txt", "DUP", None),
+                entry("d/renamed.jpg", "JPG", None),
+                entry("d/z.bin", "BIN", None),
+                entry("new.txt", "NEW", None),
+            ]
+        };
+
+        let report = Report::new(&from, &to);
+        assert_eq!(report.moved, vec![("a/dup.txt", "d/dup.txt"), ("a/photo.jpg", "d/renamed.jpg")]);
+        assert_eq!(report.removed, vec!["b/dup.txt", "c/x.bin", "c/y.bin", "lost.txt"]);
+        assert_eq!(report.added, vec!["d/z.bin", "new.txt"]);
+    }
+
+    #[test]
+    fn can_report_dirs() {
+        let from = ControlFile {
+            header: Header::default(),
+            entries: vec![
+                ControlFileEntry {
+                    file_path: "ABC/".to_owned(),
+                    sha256: vec![],
+                    stat: None,
+                    metadata: None,
+                },
+                ControlFileEntry {
+                    file_path: "DEF/".to_owned(),
+                    sha256: vec![],
+                    stat: None,
+                    metadata: None,
+                },
+            ]
+        };
+
+        let to = ControlFile {
+            header: Header::default(),
+            entries: vec![
+                ControlFileEntry {
+                    file_path: "DEF/".to_owned(),
+                    sha256: vec![],
+                    stat: None,
+                    metadata: None,
+                },
+                ControlFileEntry {
+                    file_path: "XYZ/".to_owned(),
+                    sha256: vec![],
+                    stat: None,
+                    metadata: None,
+                },
+                ControlFileEntry {
+                    file_path: "XYZ/A".to_owned(),
+                    sha256: str_hash("A"),
+                    stat: None,
+                    metadata: None,
+                },
+            ]
+        };
+
+        let report = Report::new(&from, &to);
+        assert_eq!(report.added, vec!["XYZ/A"]);
+        assert_eq!(report.added_dirs, vec!["XYZ/"]);
+        assert_eq!(report.removed.len(), 0);
+        assert_eq!(report.removed_dirs, vec!["ABC/"]);
+    }
+
+    #[test]
+    fn can_report_metadata_change() {
+        let meta = |mode| Some(Metadata { mode, uid: 0, gid: 0, xattr: str_hash("") });
+        let from = ControlFile {
+            header: Header::default(),
+            entries: vec![
+                ControlFileEntry {
+                    file_path: "ABC".to_owned(),
+                    sha256: str_hash("ABC"),
+                    stat: None,
+                    metadata: meta(0o644),
+                },
+                ControlFileEntry {
+                    file_path: "DEF".to_owned(),
+                    sha256: str_hash("DEF"),
+                    stat: None,
+                    metadata: meta(0o644),
+                },
+                ControlFileEntry {
+                    file_path: "EFG".to_owned(),
+                    sha256: str_hash("EFG"),
+                    stat: None,
+                    metadata: None,
+                },
+            ]
+        };
+
+        let to = ControlFile {
+            header: Header::default(),
+            entries: vec![
+                ControlFileEntry {
+                    file_path: "ABC".to_owned(),
+                    sha256: str_hash("ABC"),
+                    stat: None,
+                    metadata: meta(0o600),
+                },
+                ControlFileEntry {
+                    file_path: "DEF".to_owned(),
+                    sha256: str_hash("DEF0"),
+                    stat: None,
+                    metadata: meta(0o600),
+                },
+                ControlFileEntry {
+                    file_path: "EFG".to_owned(),
+                    sha256: str_hash("EFG"),
+                    stat: None,
+                    metadata: meta(0o600),
+                },
+            ]
+        };
+
+        let report = Report::new(&from, &to);
+        assert_eq!(report.modified, vec!["DEF"]);
+        assert_eq!(report.metadata_changed, vec!["ABC"]);
+    }
+
+    #[test]
+    fn can_report_suspected_corruption() {
+        let from = ControlFile {
+            header: Header::default(),
+            entries: vec![
+                entry("ABC", "ABC", Some(100)),
+                entry("DEF", "DEF", Some(100)),
+                entry("EFG", "EFG", None),
+                entry("HIJ", "HIJ", Some(100)),
+            ]
+        };
+        let to = ControlFile {
+            header: Header::default(),
+            entries: vec![
+                entry("ABC", "ABD", Some(100)),
+                entry("DEF", "DEG", Some(200)),
+                entry("EFG", "EFH", Some(100)),
+                entry("HIJ", "HIJ", Some(100)),
+            ]
+        };
+
+        let report = Report::new(&from, &to);
+        assert_eq!(report.corrupted, vec!["ABC"]);
+        assert_eq!(report.modified, vec!["DEF", "EFG"]);
+        assert_eq!(report.summary("").corrupted, 1);
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+
+use crate::{control_file::ControlFileEntry, report::{Report, CORRUPTED}, scan_log::ScanLog};
+
+/// Kinds of change that are listed file by file. Others are only counted since they are usually intended.
+const INTERESTING: [&str; 4] = [CORRUPTED, "removed", "removed_dir", "error"];
+
+/// Changes under a directory prefix.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RollupDir<'a> {
+    /// Ends with '/'. Empty for paths at the top.
+    pub dir: String,
+    /// Kind of change, number of changes and total size of the files, in the order of kinds in the report.
+    pub groups: Vec<(&'static str, usize, u64)>,
+    /// All changes under the directory as (kind, path). Empty unless any of them is interesting.
+    pub changes: Vec<(&'static str, &'a str)>,
+}
+
+/// Groups changes and unreadable files by the first 'depth' directories of their paths.
+pub fn rollup<'a>(report: &Report<'a>, log: &'a ScanLog, depth: usize) -> Vec<RollupDir<'a>> {
+    let mut items: Vec<(&'static str, &'a str, u64)> = report.changes().into_iter().map(|c| {
+        let entry = if c.kind == "removed" { report.from.get(c.old_path) } else { report.to.get(c.path) };
+        (c.kind, c.path, size_of(entry))
+    }).collect();
+    items.extend(log.errors.iter().map(|e| ("error", e.path.as_str(), size_of(report.from.get(&e.path)))));
+
+    let mut dirs: BTreeMap<&'a str, RollupDir<'a>> = BTreeMap::new();
+    for (kind, path, bytes) in items.into_iter() {
+        let dir = dir_prefix(path, depth);
+        let d = dirs.entry(dir).or_insert_with(|| RollupDir { dir: dir.to_owned(), groups: vec![], changes: vec![] });
+        match d.groups.iter_mut().find(|g| g.0 == kind) {
+            Some(g) => { g.1 += 1; g.2 += bytes; }
+            None => d.groups.push((kind, 1, bytes)),
+        }
+        d.changes.push((kind, path));
+    }
+
+    dirs.into_values().map(|mut d| {
+        if ! d.groups.iter().any(|g| INTERESTING.contains(&g.0)) {
+            d.changes.clear();
+        }
+        d.changes.sort_by(|a, b| a.1.cmp(b.1));
+        d
+    }).collect()
+}
+
+fn size_of(e: Option<&ControlFileEntry>) -> u64 {
+    e.and_then(|e| e.stat.as_ref()).map(|s| s.size).unwrap_or(0)
+}
+
+/// First 'depth' directories of the parent of the path including the trailing '/'.
+fn dir_prefix(path: &str, depth: usize) -> &str {
+    let parent = match path.trim_end_matches('/').rfind('/') {
+        Some(idx) => &path[..idx + 1],
+        None => return "",
+    };
+    if depth == 0 {
+        return "";
+    }
+    match parent.match_indices('/').nth(depth - 1) {
+        Some((idx, _)) => &parent[..idx + 1],
+        None => parent,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control_file::{ControlFile, Header, entry};
+    use crate::{report::Report, scan_log::{ScanLog, ScanError}};
+    use super::{dir_prefix, rollup, RollupDir};
+
+    #[test]
+    fn can_get_dir_prefix() {
+        assert_eq!(dir_prefix("a/b/c/d.txt", 2), "a/b/");
+        assert_eq!(dir_prefix("a/b/c/d.txt", 5), "a/b/c/");
+        assert_eq!(dir_prefix("a/b/c/", 2), "a/b/");
+        assert_eq!(dir_prefix("a/b/c/", 1), "a/");
+        assert_eq!(dir_prefix("d.txt", 1), "");
+        assert_eq!(dir_prefix("a/d.txt", 0), "");
+    }
+
+    #[test]
+    fn can_rollup() {
+        let from = ControlFile {
+            header: Header::default(),
+            entries: vec![entry("a/x/1", "1", Some(1)), entry("a/x/2", "2", Some(1)), entry("b/1", "1", Some(1)), entry("b/2", "22", Some(1)), entry("b/3", "3", Some(1))],
+        };
+        let to = ControlFile {
+            header: Header::default(),
+            entries: vec![entry("a/x/1", "10", Some(1)), entry("a/x/2", "20", Some(1)), entry("b/1", "1", Some(1)), entry("b/3", "3", Some(1)), entry("c", "c", Some(1))],
+        };
+        let report = Report::new(&from, &to);
+        let log = ScanLog { errors: vec![ScanError { path: "b/3".to_owned(), message: "denied".to_owned() }], ..ScanLog::default() };
+
+        assert_eq!(
+            rollup(&report, &log, 1),
+            vec![
+                RollupDir { dir: "".to_owned(), groups: vec![("added", 1, 1)], changes: vec![] },
+                RollupDir { dir: "a/".to_owned(), groups: vec![("modified", 2, 4)], changes: vec![] },
+                RollupDir {
+                    dir: "b/".to_owned(), groups: vec![("removed", 1, 2), ("error", 1, 1)],
+                    changes: vec![("removed", "b/2"), ("error", "b/3")],
+                },
+            ]
+        );
+        assert_eq!(rollup(&report, &log, 2)[1].dir, "a/x/");
+    }
+}
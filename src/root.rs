@@ -0,0 +1,80 @@
+use std::{path::{Path, PathBuf}, collections::HashSet};
+
+use serde::Serialize;
+
+/// A target directory to be scrubbed. Entries under a named root are recorded with "NAME/" prefix.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Root {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl Root {
+    pub fn new<P: AsRef<Path>>(name: &str, path: P) -> Self {
+        Self { name: name.to_owned(), path: path.as_ref().to_owned() }
+    }
+
+    /// Builds roots from command line arguments. Each argument is either 'NAME=PATH' or 'PATH'.
+    /// A single unnamed root has no prefix so that Controlfiles of older versions can be used as is.
+    /// Otherwise unnamed roots are named after the last component of their path.
+    pub fn from_args(args: &[String]) -> Result<Vec<Self>, String> {
+        let mut roots: Vec<Self> = vec![];
+        for arg in args.iter() {
+            let root = match arg.split_once('=') {
+                Some((name, path)) if ! name.is_empty() && ! name.contains('/') => Self::new(name, path),
+                _ if args.len() == 1 => Self::new("", arg),
+                _ => {
+                    let name = Path::new(arg).canonicalize().ok()
+                        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+                        .ok_or_else(|| format!("Cannot determine root name of '{}'. Specify it as NAME={}.", arg, arg))?;
+                    Self::new(&name, arg)
+                }
+            };
+            roots.push(root);
+        }
+
+        let mut names = HashSet::new();
+        for r in roots.iter() {
+            if ! names.insert(&r.name) {
+                return Err(format!("Duplicated root name '{}'. Specify it as NAME=PATH.", r.name));
+            }
+        }
+
+        Ok(roots)
+    }
+
+    /// Prefix of entries under this root in the Controlfile.
+    pub fn prefix(&self) -> String {
+        if self.name.is_empty() { String::new() } else { format!("{}/", self.name) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::tempdir;
+    use super::Root;
+
+    #[test]
+    fn single_root_has_no_prefix() {
+        let roots = Root::from_args(&["/foo/bar".to_owned()]).unwrap();
+        assert_eq!(roots, vec![Root::new("", "/foo/bar")]);
+        assert_eq!(roots[0].prefix(), "");
+    }
+
+    #[test]
+    fn can_name_roots() {
+        let tmp_dir = tempdir().unwrap();
+        fs::create_dir(tmp_dir.path().join("photos")).unwrap();
+        let photos = tmp_dir.path().join("photos").to_string_lossy().to_string();
+
+        let roots = Root::from_args(&["music=/mnt/music".to_owned(), photos.clone()]).unwrap();
+        assert_eq!(roots, vec![Root::new("music", "/mnt/music"), Root::new("photos", &photos)]);
+        assert_eq!(roots[0].prefix(), "music/");
+    }
+
+    #[test]
+    fn duplicated_names() {
+        assert!(Root::from_args(&["a=/foo".to_owned(), "a=/bar".to_owned()]).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use std::{fs::Metadata, os::unix::fs::FileTypeExt, time::{Duration, SystemTime}};
+
+use clap::ValueEnum;
+use regex::Regex;
+
+/// Kind of a directory entry for '--exclude-type'. Symbolic links are not followed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FileKind {
+    File,
+    Dir,
+    Symlink,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+}
+
+impl FileKind {
+    fn of(meta: &Metadata) -> Self {
+        let t = meta.file_type();
+        if t.is_symlink() { FileKind::Symlink }
+        else if t.is_dir() { FileKind::Dir }
+        else if t.is_fifo() { FileKind::Fifo }
+        else if t.is_socket() { FileKind::Socket }
+        else if t.is_block_device() { FileKind::BlockDevice }
+        else if t.is_char_device() { FileKind::CharDevice }
+        else { FileKind::File }
+    }
+}
+
+/// Exclusion rule that is evaluated in addition to exclude patterns.
+pub enum Rule {
+    /// Matched against the relative path. Directories end with '/'.
+    Regex(Regex),
+    /// Files larger than the size in bytes.
+    LargerThan(u64),
+    /// Files modified after the time, which is the duration before the scan.
+    NewerThan(SystemTime, Duration),
+    Kind(FileKind),
+}
+
+impl Rule {
+    /// `meta` follows symbolic links whereas `link_meta` does not.
+    pub fn matches(&self, path: &str, meta: &Metadata, link_meta: &Metadata) -> bool {
+        match self {
+            Rule::Regex(re) => re.is_match(path),
+            Rule::LargerThan(size) => meta.is_file() && *size < meta.len(),
+            Rule::NewerThan(time, _) => meta.is_file() && meta.modified().map(|m| *time < m).unwrap_or(false),
+            Rule::Kind(kind) => FileKind::of(link_meta) == *kind,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Rule::Regex(re) => format!("--exclude-regex '{}'", re),
+            Rule::LargerThan(size) => format!("--exclude-larger-than {}", size),
+            Rule::NewerThan(_, duration) => format!("--exclude-newer-than {}s", duration.as_secs()),
+            Rule::Kind(kind) => format!("--exclude-type {:?}", kind),
+        }
+    }
+}
+
+/// Parses size such as '1024', '10K', '50G'. Units are powers of 1024.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let s = s.strip_suffix(['b', 'B']).unwrap_or(s);
+    let s = s.strip_suffix(['i']).unwrap_or(s);
+    let (num, unit) = match s.char_indices().last() {
+        Some((idx, c)) if c.is_ascii_alphabetic() => (&s[..idx], c.to_ascii_uppercase()),
+        _ => (s, ' '),
+    };
+    let shift = match unit {
+        ' ' => 0,
+        'K' => 10,
+        'M' => 20,
+        'G' => 30,
+        'T' => 40,
+        _ => return Err(format!("Invalid size unit '{}'.", unit)),
+    };
+    let num = num.trim().parse::<u64>().map_err(|_| format!("Invalid size '{}'.", num))?;
+    num.checked_mul(1u64 << shift).ok_or_else(|| format!("Size is too large '{}'.", s))
+}
+
+/// Parses duration such as '30s', '10m', '2h', '1d', '1w'. Number without unit means seconds.
+/// The duration must not go back from now beyond the time the system can represent.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (num, unit) = match s.char_indices().last() {
+        Some((idx, c)) if c.is_ascii_alphabetic() => (&s[..idx], c),
+        _ => (s, 's'),
+    };
+    let secs = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        _ => return Err(format!("Invalid duration unit '{}'.", unit)),
+    };
+    let num = num.trim().parse::<u64>().map_err(|_| format!("Invalid duration '{}'.", num))?;
+    num.checked_mul(secs).map(Duration::from_secs)
+        .filter(|d| SystemTime::now().checked_sub(*d).is_some())
+        .ok_or_else(|| format!("Duration '{}' is too large.", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::{self, File}, time::{Duration, SystemTime}};
+    use regex::Regex;
+    use tempfile::tempdir;
+    use super::{parse_size, parse_duration, Rule, FileKind};
+
+    #[test]
+    fn can_parse_size() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("10K"), Ok(10 * 1024));
+        assert_eq!(parse_size("50G"), Ok(50 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("2MiB"), Ok(2 * 1024 * 1024));
+        assert_eq!(parse_size("1m"), Ok(1024 * 1024));
+        assert!(parse_size("1X").is_err());
+        assert!(parse_size("G").is_err());
+    }
+
+    #[test]
+    fn can_parse_duration() {
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("10m"), Ok(Duration::from_secs(600)));
+        assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(86400)));
+        assert!(parse_duration("1y").is_err());
+        assert_eq!(parse_duration("18446744073709551615w"), Err("Duration '18446744073709551615w' is too large.".to_owned()));
+        assert_eq!(parse_duration("18446744073709551615"), Err("Duration '18446744073709551615' is too large.".to_owned()));
+    }
+
+    #[test]
+    fn rules_match() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("foo.txt");
+        fs::write(&path, b"0123456789").unwrap();
+        let meta = fs::metadata(&path).unwrap();
+        let dir_meta = fs::metadata(tmp_dir.path()).unwrap();
+
+        assert!(Rule::Regex(Regex::new(r"\.txt$").unwrap()).matches("foo.txt", &meta, &meta));
+        assert!(!Rule::Regex(Regex::new(r"^bar").unwrap()).matches("foo.txt", &meta, &meta));
+        assert!(Rule::LargerThan(9).matches("foo.txt", &meta, &meta));
+        assert!(!Rule::LargerThan(10).matches("foo.txt", &meta, &meta));
+        assert!(!Rule::LargerThan(0).matches("foo/", &dir_meta, &dir_meta));
+        let recent = SystemTime::now() - Duration::from_secs(600);
+        assert!(Rule::NewerThan(recent, Duration::from_secs(600)).matches("foo.txt", &meta, &meta));
+        assert!(!Rule::NewerThan(SystemTime::now() + Duration::from_secs(600), Duration::ZERO).matches("foo.txt", &meta, &meta));
+        assert!(Rule::Kind(FileKind::File).matches("foo.txt", &meta, &meta));
+        assert!(Rule::Kind(FileKind::Dir).matches("foo/", &dir_meta, &dir_meta));
+
+        File::create(tmp_dir.path().join("bar.txt")).unwrap();
+        std::os::unix::fs::symlink(tmp_dir.path().join("bar.txt"), tmp_dir.path().join("link")).unwrap();
+        let link_meta = fs::symlink_metadata(tmp_dir.path().join("link")).unwrap();
+        assert!(Rule::Kind(FileKind::Symlink).matches("link", &meta, &link_meta));
+    }
+}
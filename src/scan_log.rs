@@ -0,0 +1,53 @@
+use serde::Serialize;
+
+use crate::marker::SkipReason;
+
+/// What happened during a scan apart from the entries recorded in the Controlfile.
+#[derive(Debug, Default, Serialize)]
+pub struct ScanLog {
+    /// Paths left out of the scan. Only collected when asked.
+    pub excluded: Vec<Excluded>,
+    pub skipped_cache_dirs: usize,
+    pub skipped_marked_dirs: usize,
+    pub skipped_nodump_files: usize,
+    /// Control file and other state files of this tool found under the target.
+    pub state_files: Vec<String>,
+    /// Files that could not be read. Their previous entries are kept in the Controlfile.
+    pub errors: Vec<ScanError>,
+    /// Amount of work for each root in the order of scan.
+    pub roots: Vec<RootScan>,
+}
+
+impl ScanLog {
+    pub fn count_skipped(&mut self, reason: &SkipReason) {
+        match reason {
+            SkipReason::CacheDirTag => self.skipped_cache_dirs += 1,
+            SkipReason::Marker(_) => self.skipped_marked_dirs += 1,
+            SkipReason::NoDump => self.skipped_nodump_files += 1,
+            SkipReason::StateFile => {},
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Excluded {
+    pub path: String,
+    /// Description of the rule that excluded the path.
+    pub rule: String,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct ScanError {
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RootScan {
+    pub name: String,
+    /// Number of files hashed.
+    pub files: usize,
+    /// Total size of files hashed.
+    pub bytes: u64,
+    pub seconds: f64,
+}
@@ -0,0 +1,158 @@
+use std::{fs, io::{self, Write}};
+
+use minijinja::{syntax::SyntaxConfig, value::Serde, Environment, UndefinedBehavior};
+use serde::Serialize;
+
+use crate::{
+    control_file::ControlFileEntry, output::{Renderer, Run}, report::{Change, Report, Summary},
+    scan_log::{ScanError, ScanLog},
+};
+
+/// Values available in a template. Each list of changes has the fields of FileChange.
+#[derive(Serialize)]
+struct Context<'a> {
+    run: &'a Run,
+    summary: Summary,
+    roots: Vec<RootSummary<'a>>,
+    /// All changes. The lists below have the changes of each category.
+    changes: Vec<FileChange<'a>>,
+    added: Vec<FileChange<'a>>,
+    removed: Vec<FileChange<'a>>,
+    modified: Vec<FileChange<'a>>,
+    corrupted: Vec<FileChange<'a>>,
+    metadata_changed: Vec<FileChange<'a>>,
+    added_dirs: Vec<FileChange<'a>>,
+    removed_dirs: Vec<FileChange<'a>>,
+    normalization_matches: Vec<FileChange<'a>>,
+    moved: Vec<FileChange<'a>>,
+    errors: &'a [ScanError],
+    case_collisions: &'a [Vec<&'a str>],
+}
+
+#[derive(Serialize)]
+struct RootSummary<'a> {
+    name: &'a str,
+    path: String,
+    summary: Summary,
+}
+
+#[derive(Clone, Serialize)]
+struct FileChange<'a> {
+    #[serde(flatten)]
+    change: Change<'a>,
+    old_size: Option<u64>,
+    new_size: Option<u64>,
+    old_mtime: Option<String>,
+    new_mtime: Option<String>,
+}
+
+/// Template of a report in the syntax of Jinja2. Block tags do not leave empty lines so that text layouts are easy to write.
+/// Referring to an undefined value is an error so that typos are found at the first run.
+pub struct Template {
+    env: Environment<'static>,
+    name: String,
+}
+
+impl Template {
+    pub fn load(path: &str) -> io::Result<Self> {
+        let source = fs::read_to_string(path)?;
+        Self::new(path, source).map_err(io::Error::other)
+    }
+
+    /// HTML special characters are escaped if the name ends with '.html'.
+    pub fn new(name: &str, source: String) -> Result<Self, minijinja::Error> {
+        let mut env = Environment::new();
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        env.set_syntax(SyntaxConfig::builder().trim_blocks(true).lstrip_blocks(true).keep_trailing_newline(true).build()?);
+        env.add_template_owned(name.to_owned(), source)?;
+
+        Ok(Self { env, name: name.to_owned() })
+    }
+
+    pub fn render(&self, report: &Report, log: &ScanLog, run: &Run) -> Result<String, minijinja::Error> {
+        let changes: Vec<FileChange> = report.changes().into_iter().map(|c| {
+            let old = stat_of(report.from.get(c.old_path));
+            let new = stat_of(report.to.get(c.path));
+            FileChange { change: c, old_size: old.0, new_size: new.0, old_mtime: old.1, new_mtime: new.1 }
+        }).collect();
+        let of_kind = |kind: &str| changes.iter().filter(|c| c.change.kind == kind).cloned().collect::<Vec<_>>();
+
+        let ctx = Context {
+            run,
+            summary: report.summary(""),
+            roots: run.roots.iter().map(|r| RootSummary {
+                name: &r.name, path: r.path.to_string_lossy().to_string(), summary: report.summary(&r.prefix()),
+            }).collect(),
+            added: of_kind("added"),
+            removed: of_kind("removed"),
+            modified: of_kind("modified"),
+            corrupted: of_kind("corrupted"),
+            metadata_changed: of_kind("metadata_changed"),
+            added_dirs: of_kind("added_dir"),
+            removed_dirs: of_kind("removed_dir"),
+            normalization_matches: of_kind("normalization_match"),
+            moved: of_kind("moved"),
+            changes,
+            errors: &log.errors,
+            case_collisions: &report.case_collisions,
+        };
+
+        self.env.get_template(&self.name)?.render(Serde(ctx))
+    }
+}
+
+fn stat_of(e: Option<&ControlFileEntry>) -> (Option<u64>, Option<String>) {
+    match e.and_then(|e| e.stat.as_ref()) {
+        Some(s) => (Some(s.size), Some(s.mtime())),
+        None => (None, None),
+    }
+}
+
+pub struct TemplateRenderer<W: Write> {
+    pub out: W,
+    pub template: Template,
+}
+
+impl<W: Write> Renderer for TemplateRenderer<W> {
+    fn render(&mut self, report: &Report, log: &ScanLog, run: &Run) -> io::Result<()> {
+        let text = self.template.render(report, log, run).map_err(io::Error::other)?;
+        self.out.write_all(text.as_bytes())?;
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+    use crate::control_file::{ControlFile, Header, entry};
+    use crate::{output::Run, report::Report, root::Root, scan_log::ScanLog};
+    use super::Template;
+
+    #[test]
+    fn can_render_template() {
+        let from = ControlFile { header: Header::default(), entries: vec![entry("a", "a", Some(1)), entry("b", "b", Some(1))] };
+        let to = ControlFile { header: Header::default(), entries: vec![entry("a", "a0", Some(1)), entry("c<", "c", Some(1))] };
+        let report = Report::new(&from, &to);
+        let run = Run::new(SystemTime::now(), "Controlfile", &[Root::new("", "/tmp")], false);
+
+        let source = "\
+Scrub of {{ run.roots[0].path }} by {{ run.control_file }}: {{ summary.added }} added
+{% for c in modified %}
+* {{ c.path }} {{ c.old_size }} -> {{ c.new_size }}
+{% endfor %}
+{% for c in changes %}{{ c.kind }} {% endfor %}
+";
+        let template = Template::new("mail.txt", source.to_owned()).unwrap();
+        assert_eq!(
+            template.render(&report, &ScanLog::default(), &run).unwrap(),
+            "Scrub of /tmp by Controlfile: 1 added\n* a 1 -> 2\nadded removed modified "
+        );
+
+        let template = Template::new("mail.html", "{{ added[0].path }}".to_owned()).unwrap();
+        assert_eq!(template.render(&report, &ScanLog::default(), &run).unwrap(), "c&lt;");
+
+        let template = Template::new("typo.txt", "{{ summary.addded }}".to_owned()).unwrap();
+        assert!(template.render(&report, &ScanLog::default(), &run).is_err());
+        assert!(Template::new("broken.txt", "{% for %}".to_owned()).is_err());
+    }
+}
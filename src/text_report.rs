@@ -0,0 +1,156 @@
+use std::io::{self, Write};
+
+use crate::{output::{Renderer, Run}, report::{Report, Summary}, rollup, scan_log::ScanLog, Cli};
+
+/// Human readable layout. Paths are quoted so that unusual characters are visible.
+pub struct TextRenderer<W: Write> {
+    out: W,
+    show_excluded: bool,
+    exclude_caches: bool,
+    exclude_marker: bool,
+    exclude_nodump: bool,
+    /// Depth of directories to group changes by. Changes are listed by kind if None.
+    rollup: Option<usize>,
+}
+
+impl<W: Write> TextRenderer<W> {
+    pub fn new(out: W, cli: &Cli) -> Self {
+        Self {
+            out,
+            show_excluded: cli.show_excluded,
+            exclude_caches: cli.exclude_caches,
+            exclude_marker: ! cli.exclude_marker.is_empty(),
+            exclude_nodump: cli.exclude_nodump,
+            rollup: cli.rollup,
+        }
+    }
+}
+
+impl<W: Write> Renderer for TextRenderer<W> {
+    fn render(&mut self, report: &Report, log: &ScanLog, run: &Run) -> io::Result<()> {
+        let w = &mut self.out;
+        if ! report.corrupted.is_empty() {
+            writeln!(w, "!!! Suspected corruption: {} file(s) changed content without changing size or modification time !!!", report.corrupted.len())?;
+            writeln!(w)?;
+        }
+        writeln!(w, "Summary:")?;
+        write_summary(w, &report.summary(""), "  ")?;
+        if 1 < run.roots.len() {
+            for r in run.roots.iter() {
+                writeln!(w, "  [{}]", r.name)?;
+                write_summary(w, &report.summary(&r.prefix()), "    ")?;
+            }
+        }
+        for f in log.state_files.iter() {
+            writeln!(w, "  Automatically excluded state file: {:?}", f)?;
+        }
+        if self.exclude_caches {
+            writeln!(w, "  Skipped cache directories: {}", log.skipped_cache_dirs)?;
+        }
+        if self.exclude_marker {
+            writeln!(w, "  Skipped marked directories: {}", log.skipped_marked_dirs)?;
+        }
+        if self.exclude_nodump {
+            writeln!(w, "  Skipped nodump files: {}", log.skipped_nodump_files)?;
+        }
+        if ! log.errors.is_empty() {
+            writeln!(w, "  Unreadable files: {}", log.errors.len())?;
+        }
+
+        writeln!(w)?;
+        writeln!(w, "Details:")?;
+        match self.rollup {
+            Some(depth) => write_rollup(w, report, log, depth)?,
+            None => write_changes(w, report)?,
+        }
+
+        if ! log.errors.is_empty() {
+            writeln!(w, "[Unreadable files]")?;
+            for e in log.errors.iter() {
+                writeln!(w, "  {:?}: {}", e.path, e.message)?;
+            }
+        }
+
+        if self.show_excluded {
+            writeln!(w, "[Excluded]")?;
+            for e in log.excluded.iter() {
+                writeln!(w, "  {:?} by {}", e.path, e.rule)?;
+            }
+        }
+
+        w.flush()
+    }
+}
+
+fn write_summary<W: Write + ?Sized>(w: &mut W, summary: &Summary, indent: &str) -> io::Result<()> {
+    writeln!(w, "{}Added files: {}", indent, summary.added)?;
+    writeln!(w, "{}Removed files: {}", indent, summary.removed)?;
+    writeln!(w, "{}Modified files: {}", indent, summary.modified)?;
+    writeln!(w, "{}Suspected corruption: {}", indent, summary.corrupted)?;
+    writeln!(w, "{}Metadata changed files: {}", indent, summary.metadata_changed)?;
+    writeln!(w, "{}Added directories: {}", indent, summary.added_dirs)?;
+    writeln!(w, "{}Removed directories: {}", indent, summary.removed_dirs)?;
+    writeln!(w, "{}Normalization/case matches: {}", indent, summary.normalization_matches)?;
+    writeln!(w, "{}Moved/renamed files: {}", indent, summary.moved)?;
+    Ok(())
+}
+
+fn write_changes<W: Write + ?Sized>(w: &mut W, report: &Report) -> io::Result<()> {
+    writeln!(w, "[Added files]")?;
+    for f in report.added.iter() {
+        writeln!(w, "  {:?}", f)?;
+    }
+
+    writeln!(w, "[Removed files]")?;
+    for f in report.removed.iter() {
+        writeln!(w, "  {:?}", f)?;
+    }
+
+    writeln!(w, "[Modified files]")?;
+    for f in report.modified.iter() {
+        writeln!(w, "  {:?}", f)?;
+    }
+
+    writeln!(w, "[Suspected corruption]")?;
+    for f in report.corrupted.iter() {
+        writeln!(w, "  {:?}", f)?;
+    }
+
+    writeln!(w, "[Metadata changed files]")?;
+    for f in report.metadata_changed.iter() {
+        writeln!(w, "  {:?}", f)?;
+    }
+
+    writeln!(w, "[Added directories]")?;
+    for f in report.added_dirs.iter() {
+        writeln!(w, "  {:?}", f)?;
+    }
+
+    writeln!(w, "[Removed directories]")?;
+    for f in report.removed_dirs.iter() {
+        writeln!(w, "  {:?}", f)?;
+    }
+
+    writeln!(w, "[Normalization/case matches]")?;
+    for (old, new) in report.normalization_matches.iter() {
+        writeln!(w, "  {:?} -> {:?}", old, new)?;
+    }
+
+    writeln!(w, "[Moved/renamed files]")?;
+    for (old, new) in report.moved.iter() {
+        writeln!(w, "  {:?} -> {:?}", old, new)?;
+    }
+    Ok(())
+}
+
+fn write_rollup<W: Write + ?Sized>(w: &mut W, report: &Report, log: &ScanLog, depth: usize) -> io::Result<()> {
+    writeln!(w, "[Changes by directory]")?;
+    for d in rollup::rollup(report, log, depth).iter() {
+        let groups: Vec<String> = d.groups.iter().map(|(kind, count, bytes)| format!("{} {} ({} bytes)", kind, count, bytes)).collect();
+        writeln!(w, "  {:?}: {}", d.dir, groups.join(", "))?;
+        for (kind, path) in d.changes.iter() {
+            writeln!(w, "    {} {:?}", kind, path)?;
+        }
+    }
+    Ok(())
+}
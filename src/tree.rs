@@ -1,8 +1,40 @@
-use std::{path::Path, io::{Error, ErrorKind}, fs};
+use std::{path::{Path, PathBuf}, io::{Error, ErrorKind}, fs, collections::VecDeque, thread};
+use std::sync::{Arc, Mutex, Condvar, mpsc::{self, SyncSender}};
 
-use crate::{io_error::IoError, Cli, exclude::Exclude};
+use crate::{io_error::IoError, Cli, exclude::{Exclude, Include, IGNORE_FILE_NAME}, marker::{self, SkipReason}};
 
+const WORKER_COUNT_MAX: usize = 8;
+const DIR_QUEUE_CAPACITY: usize = 1024;
+const ENTRY_CHANNEL_CAPACITY: usize = 1024;
+const NOT_INCLUDED: &str = "no -I pattern";
+
+#[cfg(test)]
 pub fn list_recursive<P: AsRef<Path>>(dir: P, cli: &Cli) -> Result<Vec<String>, IoError> {
+    let mut ret: Vec<String> = vec![];
+    walk(dir, cli, |e| {
+        if let WalkEntry::Found(path) = e {
+            ret.push(path);
+        }
+        Ok(())
+    })?;
+    Ok(ret)
+}
+
+pub enum WalkEntry {
+    /// Relative path of a file or a directory. Directories end with '/'.
+    Found(String),
+    /// Relative path left out of the walk and the description of the rule that excluded it.
+    Excluded(String, String),
+    /// Relative path left out of the walk because of a marker or an attribute.
+    Skipped(String, SkipReason),
+}
+
+/// Walks the directory with worker threads and passes each entry to `consume` on the calling thread as soon as it is found.
+/// Entries come in no particular order. '.scrubignore' files are honored in every directory.
+/// If `consume` fails, the walk is aborted and the error is returned.
+pub fn walk<P, C>(dir: P, cli: &Cli, mut consume: C) -> Result<(), IoError>
+    where P: AsRef<Path>, C: FnMut(WalkEntry) -> Result<(), IoError>
+{
     if ! dir.as_ref().exists() {
         return Err(
             IoError {
@@ -13,45 +45,229 @@ pub fn list_recursive<P: AsRef<Path>>(dir: P, cli: &Cli) -> Result<Vec<String>,
     if ! dir.as_ref().is_dir() {
         return Err(
             IoError {
-                cause: Error::new(ErrorKind::Other, "Not a directory."),
+                cause: Error::other("Not a directory."),
                 message: "Not a directory".to_owned(),
                 path: Some(dir.as_ref().to_owned()) }
         );
     }
-    let mut ret: Vec<String> = vec![];
-    let exclude = Exclude::new(cli.exclude.clone());
+    let exclude = Exclude::from_cli(cli).map_err(|err|
+        IoError { cause: err, message: "Cannot read exclude file.".to_owned(), path: None }
+    )?;
+    let include = Include::new(&cli.include);
+    let queue = WorkQueue::new(dir.as_ref().to_owned(), Arc::new(exclude));
+    let (tx, rx) = mpsc::sync_channel::<Result<WalkEntry, IoError>>(ENTRY_CHANNEL_CAPACITY);
+    let worker_count = thread::available_parallelism().map(|n| n.get()).unwrap_or(1).min(WORKER_COUNT_MAX);
+
+    let state_files = state_files_under(dir.as_ref(), cli);
+    let ctx = &Context { root: dir.as_ref(), cli, include: &include, queue: &queue, state_files: &state_files };
+
+    thread::scope(|s| {
+        for _ in 0..worker_count {
+            let tx = tx.clone();
+            s.spawn(move || worker(ctx, tx));
+        }
+        drop(tx);
+
+        let result = rx.iter().try_for_each(|e| e.and_then(&mut consume));
+        if result.is_err() {
+            ctx.queue.abort();
+        }
+        // Unblocks workers waiting for the channel.
+        drop(rx);
+        result
+    })
+}
+
+/// A directory and the exclude patterns in effect for it.
+type Work = (PathBuf, Arc<Exclude>);
+
+struct QueueState {
+    dirs: VecDeque<Work>,
+    busy: usize,
+    aborted: bool,
+}
+
+/// Directories waiting to be read. Bounded so that a wide tree does not pile up in memory.
+struct WorkQueue {
+    state: Mutex<QueueState>,
+    cond: Condvar,
+}
 
-    fn f<P0: AsRef<Path>, P1: AsRef<Path>>(
-        root: P0, dir: P1, ret: &mut Vec<String>, exclude: &Exclude
-    ) -> Result<(), IoError> {
-        if exclude.matches(&dir.as_ref().file_name().unwrap().to_string_lossy()) {
-            return Ok(())
+impl WorkQueue {
+    fn new(root: PathBuf, exclude: Arc<Exclude>) -> Self {
+        Self {
+            state: Mutex::new(QueueState { dirs: VecDeque::from(vec![(root, exclude)]), busy: 0, aborted: false }),
+            cond: Condvar::new(),
         }
-        let read_dir = fs::read_dir(dir.as_ref()).map_err(|err|
+    }
+
+    /// Waits for a directory. Returns None when the walk is finished or aborted.
+    fn pop(&self) -> Option<Work> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.aborted { return None; }
+            if let Some(dir) = state.dirs.pop_front() {
+                state.busy += 1;
+                return Some(dir);
+            }
+            if state.busy == 0 { return None; }
+            state = self.cond.wait(state).unwrap();
+        }
+    }
+
+    /// Gives the directory back when the queue is full.
+    fn push(&self, dir: Work) -> Result<(), Work> {
+        let mut state = self.state.lock().unwrap();
+        if DIR_QUEUE_CAPACITY <= state.dirs.len() {
+            return Err(dir);
+        }
+        state.dirs.push_back(dir);
+        self.cond.notify_one();
+        Ok(())
+    }
+
+    fn done(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.busy -= 1;
+        if state.busy == 0 {
+            self.cond.notify_all();
+        }
+    }
+
+    fn abort(&self) {
+        self.state.lock().unwrap().aborted = true;
+        self.cond.notify_all();
+    }
+}
+
+/// What workers share during a walk.
+struct Context<'a> {
+    root: &'a Path,
+    cli: &'a Cli,
+    include: &'a Include,
+    queue: &'a WorkQueue,
+    /// Relative paths of state files of this tool.
+    state_files: &'a [String],
+}
+
+fn worker(ctx: &Context, tx: SyncSender<Result<WalkEntry, IoError>>) {
+    while let Some(work) = ctx.queue.pop() {
+        // Directories that do not fit in the queue are read by this worker.
+        let mut local: Vec<Work> = vec![work];
+        let mut result = Ok(());
+        while let Some((d, exclude)) = local.pop() {
+            result = read_dir(ctx, &d, exclude, &mut local, &tx);
+            if result.is_err() { break; }
+        }
+        ctx.queue.done();
+
+        if let Err(err) = result {
+            if let Some(err) = err {
+                let _ = tx.send(Err(err));
+            }
+            ctx.queue.abort();
+            return;
+        }
+    }
+}
+
+/// Sends entries in the directory. Err(None) means that the receiver has gone.
+fn read_dir(
+    ctx: &Context, dir: &Path, exclude: Arc<Exclude>, local: &mut Vec<Work>, tx: &SyncSender<Result<WalkEntry, IoError>>
+) -> Result<(), Option<IoError>> {
+    let root = ctx.root;
+    let ignore_file = dir.join(IGNORE_FILE_NAME);
+    let exclude = if ignore_file.is_file() {
+        let base = dir.strip_prefix(root).unwrap().to_string_lossy().to_string();
+        let base = if base.is_empty() { base } else { base + "/" };
+        Arc::new(Exclude::read_ignore_file(&ignore_file, &base, exclude).map_err(|err|
             IoError {
-                cause: err, message: "Cannot read directory.".to_owned(), path: Some(dir.as_ref().to_owned())
+                cause: err, message: "Cannot read ignore file.".to_owned(), path: Some(ignore_file.clone())
             }
-        )?;
-        for e in read_dir {
-            let path = e.map_err(|err|
-                IoError {
-                    cause: err, message: "Cannot list entries in this directory.".to_owned(), path: Some(dir.as_ref().to_owned())
-                }
-            )?.path();
-            if path.is_dir() {
-                f(root.as_ref(), path.as_path(), ret, exclude)?
-            } else {
-                let name = path.file_name().unwrap().to_string_lossy();
-                if ! exclude.matches(&name) {
-                    ret.push(path.strip_prefix(root.as_ref()).unwrap().to_string_lossy().to_string());
-                }
+        )?)
+    } else {
+        exclude
+    };
+    let read_dir = fs::read_dir(dir).map_err(|err|
+        IoError {
+            cause: err, message: "Cannot read directory.".to_owned(), path: Some(dir.to_owned())
+        }
+    )?;
+    for e in read_dir {
+        let path = e.map_err(|err|
+            IoError {
+                cause: err, message: "Cannot list entries in this directory.".to_owned(), path: Some(dir.to_owned())
+            }
+        )?.path();
+        let rel = path.strip_prefix(root).unwrap().to_string_lossy().to_string();
+        let is_dir = path.is_dir();
+        let rel = if is_dir { rel + "/" } else { rel };
+        if ctx.state_files.contains(&rel) {
+            tx.send(Ok(WalkEntry::Skipped(rel, SkipReason::StateFile))).map_err(|_| None)?;
+            continue;
+        }
+        if let Some(p) = exclude.matching(&rel, is_dir).filter(|p| p.excludes()) {
+            tx.send(Ok(WalkEntry::Excluded(rel, p.describe()))).map_err(|_| None)?;
+            continue;
+        }
+        if exclude.has_rules() {
+            let link_meta = match fs::symlink_metadata(&path) {
+                Ok(m) => m,
+                // Removed after it was listed.
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => return Err(Some(
+                    IoError { cause: err, message: "Cannot read metadata.".to_owned(), path: Some(path.clone()) }
+                )),
+            };
+            // Dangling symbolic links are judged by the link itself.
+            let target_meta = fs::metadata(&path).unwrap_or_else(|_| link_meta.clone());
+            if let Some(r) = exclude.matching_rule(&rel, &target_meta, &link_meta) {
+                tx.send(Ok(WalkEntry::Excluded(rel, r.describe()))).map_err(|_| None)?;
+                continue;
+            }
+        }
+        if let Some(reason) = skip_reason(ctx.cli, &path, is_dir) {
+            tx.send(Ok(WalkEntry::Skipped(rel, reason))).map_err(|_| None)?;
+            continue;
+        }
+        if ! ctx.include.includes(&rel, is_dir) {
+            tx.send(Ok(WalkEntry::Excluded(rel, NOT_INCLUDED.to_owned()))).map_err(|_| None)?;
+            continue;
+        }
+        tx.send(Ok(WalkEntry::Found(rel))).map_err(|_| None)?;
+        if is_dir {
+            if let Err(work) = ctx.queue.push((path, exclude.clone())) {
+                local.push(work);
             }
         }
-    
-        Ok(())
     }
 
-    f(dir.as_ref(), dir.as_ref(), &mut ret, &exclude).map(|_| ret)
+    Ok(())
+}
+
+/// State files of this tool that are located under the directory, relative to it.
+fn state_files_under(dir: &Path, cli: &Cli) -> Vec<String> {
+    let dir = match dir.canonicalize() {
+        Ok(d) => d,
+        Err(_) => return vec![],
+    };
+    cli.state_files().iter()
+        .filter_map(|f| f.strip_prefix(&dir).ok())
+        .map(|rel| rel.to_string_lossy().to_string())
+        .collect()
+}
+
+fn skip_reason(cli: &Cli, path: &Path, is_dir: bool) -> Option<SkipReason> {
+    if is_dir {
+        if cli.exclude_caches && marker::is_cache_dir(path) {
+            return Some(SkipReason::CacheDirTag);
+        }
+        cli.exclude_marker.iter().find(|m| path.join(m).exists()).map(|m| SkipReason::Marker(m.clone()))
+    } else if cli.exclude_nodump && marker::has_nodump(path) {
+        Some(SkipReason::NoDump)
+    } else {
+        None
+    }
 }
 
 #[cfg(test)]
@@ -61,9 +277,15 @@ mod tests {
     use std::path::Path;
     use tempfile::tempdir;
 
+    use std::time::Duration;
     use crate::Cli;
+    use crate::output::parse_output_spec;
+    use regex::Regex;
+    use crate::io_error::IoError;
+    use crate::rule::FileKind;
+    use crate::marker::SkipReason;
 
-    use super::list_recursive;
+    use super::{list_recursive, walk, WalkEntry};
 
     #[test]
     fn can_read_single_dir() {
@@ -72,11 +294,7 @@ mod tests {
         File::create(tmp_dir.path().join("foo1.txt")).unwrap();
 
         let root = tmp_dir.into_path();
-        let cli = Cli {
-            control_file: "".to_owned(),
-            exclude: vec![],
-            target_dir: "".to_owned(),
-        };
+        let cli = Cli::default();
         let mut list = list_recursive(&root, &cli).unwrap();
         list.sort();
 
@@ -93,27 +311,20 @@ mod tests {
         File::create(tmp_dir.path().join("foo/foo1.txt")).unwrap();
 
         let root = tmp_dir.into_path();
-        let cli = Cli {
-            control_file: "".to_owned(),
-            exclude: vec![],
-            target_dir: "".to_owned(),
-        };
+        let cli = Cli::default();
         let mut list = list_recursive(&root, &cli).unwrap();
         list.sort();
         
-        assert_eq!(list.len(), 2);
-        assert_eq!(list[0], "foo/foo1.txt");
-        assert_eq!(list[1], "foo0.txt");
+        assert_eq!(list.len(), 3);
+        assert_eq!(list[0], "foo/");
+        assert_eq!(list[1], "foo/foo1.txt");
+        assert_eq!(list[2], "foo0.txt");
     }
 
     #[test]
     fn can_treat_empty() {
         let tmp_dir = tempdir().unwrap();
-        let cli = Cli {
-            control_file: "".to_owned(),
-            exclude: vec![],
-            target_dir: "".to_owned(),
-        };
+        let cli = Cli::default();
 
         let root = tmp_dir.into_path();
         let list = list_recursive(&root, &cli).unwrap();
@@ -122,14 +333,264 @@ mod tests {
     }
 
     #[test]
-    fn can_treat_non_exitent() {
-        let root = Path::new("non_exitent");
+    fn can_list_empty_dir() {
+        let tmp_dir = tempdir().unwrap();
+        fs::create_dir(tmp_dir.path().join("foo")).unwrap();
+        fs::create_dir(tmp_dir.path().join("foo/bar")).unwrap();
+
+        let root = tmp_dir.into_path();
+        let cli = Cli::default();
+        let mut list = list_recursive(&root, &cli).unwrap();
+        list.sort();
+
+        assert_eq!(list, vec!["foo/", "foo/bar/"]);
+    }
+
+    #[test]
+    fn can_treat_path_exclude() {
+        let tmp_dir = tempdir().unwrap();
+        for d in ["photos", "photos/cache", "videos", "videos/cache"] {
+            fs::create_dir(tmp_dir.path().join(d)).unwrap();
+        }
+        File::create(tmp_dir.path().join("photos/cache/a.jpg")).unwrap();
+        File::create(tmp_dir.path().join("videos/cache/b.mp4")).unwrap();
+        File::create(tmp_dir.path().join("videos/c.log")).unwrap();
+        File::create(tmp_dir.path().join("videos/keep.log")).unwrap();
+
+        let root = tmp_dir.into_path();
         let cli = Cli {
-            control_file: "".to_owned(),
-            exclude: vec![],
-            target_dir: "".to_owned(),
+            exclude: vec!["photos/cache/".to_owned(), "*.log".to_owned(), "!keep.log".to_owned()],
+            ..Cli::default()
         };
-        assert_eq!(list_recursive(&root, &cli).err().unwrap().cause.kind(), ErrorKind::NotFound);
+        let mut list = list_recursive(&root, &cli).unwrap();
+        list.sort();
+
+        assert_eq!(list, vec!["photos/", "videos/", "videos/cache/", "videos/cache/b.mp4", "videos/keep.log"]);
+    }
+
+    #[test]
+    fn can_treat_ignore_file() {
+        let tmp_dir = tempdir().unwrap();
+        fs::create_dir(tmp_dir.path().join("photos")).unwrap();
+        fs::create_dir(tmp_dir.path().join("photos/cache")).unwrap();
+        fs::create_dir(tmp_dir.path().join("cache")).unwrap();
+        fs::write(tmp_dir.path().join("photos/.scrubignore"), "cache/\n*.tmp\n").unwrap();
+        File::create(tmp_dir.path().join("photos/a.tmp")).unwrap();
+        File::create(tmp_dir.path().join("b.tmp")).unwrap();
+
+        let mut found: Vec<String> = vec![];
+        let mut excluded: Vec<(String, String)> = vec![];
+        walk(tmp_dir.path(), &Cli::default(), |e| {
+            match e {
+                WalkEntry::Found(path) => found.push(path),
+                WalkEntry::Excluded(path, rule) => excluded.push((path, rule)),
+                WalkEntry::Skipped(..) => {},
+            }
+            Ok(())
+        }).unwrap();
+        found.sort();
+        excluded.sort();
+
+        assert_eq!(found, vec!["b.tmp", "cache/", "photos/", "photos/.scrubignore"]);
+        assert_eq!(
+            excluded,
+            vec![
+                ("photos/a.tmp".to_owned(), "photos/.scrubignore:2 '*.tmp'".to_owned()),
+                ("photos/cache/".to_owned(), "photos/.scrubignore:1 'cache/'".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn can_treat_include() {
+        let tmp_dir = tempdir().unwrap();
+        fs::create_dir(tmp_dir.path().join("foo")).unwrap();
+        File::create(tmp_dir.path().join("foo/a.raw")).unwrap();
+        File::create(tmp_dir.path().join("foo/b.raw")).unwrap();
+        File::create(tmp_dir.path().join("foo/c.jpg")).unwrap();
+
+        let root = tmp_dir.into_path();
+        let cli = Cli {
+            include: vec!["*.raw".to_owned()],
+            exclude: vec!["b.*".to_owned()],
+            ..Cli::default()
+        };
+        let mut list = list_recursive(&root, &cli).unwrap();
+        list.sort();
+
+        assert_eq!(list, vec!["foo/", "foo/a.raw"]);
+    }
+
+    #[test]
+    fn can_treat_rules() {
+        let tmp_dir = tempdir().unwrap();
+        fs::create_dir(tmp_dir.path().join("vm")).unwrap();
+        fs::write(tmp_dir.path().join("vm/disk.img"), vec![0u8; 2048]).unwrap();
+        fs::write(tmp_dir.path().join("vm/notes.txt"), b"012").unwrap();
+        fs::write(tmp_dir.path().join("a.part"), b"012").unwrap();
+        std::os::unix::fs::symlink("vm/notes.txt", tmp_dir.path().join("link")).unwrap();
+
+        let root = tmp_dir.into_path();
+        let cli = Cli {
+            exclude_regex: vec![Regex::new(r"\.part$").unwrap()],
+            exclude_larger_than: Some(1024),
+            exclude_type: vec![FileKind::Symlink],
+            ..Cli::default()
+        };
+        let mut list = list_recursive(&root, &cli).unwrap();
+        list.sort();
+        assert_eq!(list, vec!["vm/", "vm/notes.txt"]);
+
+        let cli = Cli {
+            exclude_newer_than: Some(Duration::from_secs(600)),
+            ..Cli::default()
+        };
+        let list = list_recursive(&root, &cli).unwrap();
+        assert_eq!(list, vec!["vm/"]);
+    }
+
+    #[test]
+    fn can_treat_markers() {
+        let tmp_dir = tempdir().unwrap();
+        for d in ["cache", "build", "photos"] {
+            fs::create_dir(tmp_dir.path().join(d)).unwrap();
+            File::create(tmp_dir.path().join(d).join("a.txt")).unwrap();
+        }
+        fs::write(tmp_dir.path().join("cache/CACHEDIR.TAG"), b"Signature: 8a477f597d28d172789f06886806bc55").unwrap();
+        File::create(tmp_dir.path().join("build/.nobackup")).unwrap();
+
+        let cli = Cli {
+            exclude_caches: true,
+            exclude_marker: vec![".nobackup".to_owned()],
+            ..Cli::default()
+        };
+        let mut found: Vec<String> = vec![];
+        let mut skipped: Vec<(String, SkipReason)> = vec![];
+        walk(tmp_dir.path(), &cli, |e| {
+            match e {
+                WalkEntry::Found(path) => found.push(path),
+                WalkEntry::Skipped(path, reason) => skipped.push((path, reason)),
+                WalkEntry::Excluded(..) => {},
+            }
+            Ok(())
+        }).unwrap();
+        found.sort();
+        skipped.sort_by(|a, b| a.0.cmp(&b.0));
+
+        assert_eq!(found, vec!["photos/", "photos/a.txt"]);
+        assert_eq!(
+            skipped,
+            vec![
+                ("build/".to_owned(), SkipReason::Marker(".nobackup".to_owned())),
+                ("cache/".to_owned(), SkipReason::CacheDirTag),
+            ]
+        );
+    }
+
+    #[test]
+    fn state_files_are_excluded() {
+        let tmp_dir = tempdir().unwrap();
+        fs::create_dir(tmp_dir.path().join("foo")).unwrap();
+        File::create(tmp_dir.path().join("foo/Controlfile")).unwrap();
+        File::create(tmp_dir.path().join("foo/a.txt")).unwrap();
+
+        let cli = Cli {
+            control_file: tmp_dir.path().join("foo/../foo/Controlfile").to_string_lossy().to_string(),
+            ..Cli::default()
+        };
+        let mut list = list_recursive(tmp_dir.path(), &cli).unwrap();
+        list.sort();
+        assert_eq!(list, vec!["foo/", "foo/a.txt"]);
+
+        let cli = Cli {
+            control_file: tmp_dir.path().join("foo/new").to_string_lossy().to_string(),
+            ..Cli::default()
+        };
+        File::create(tmp_dir.path().join("foo/new")).unwrap();
+        let list = list_recursive(tmp_dir.path().join("foo"), &cli).unwrap();
+        assert_eq!(list.len(), 2);
+    }
+
+    #[test]
+    fn output_files_are_excluded() {
+        let tmp_dir = tempdir().unwrap();
+        File::create(tmp_dir.path().join("report.json")).unwrap();
+        File::create(tmp_dir.path().join(".report.json.tmp")).unwrap();
+        File::create(tmp_dir.path().join("a.txt")).unwrap();
+
+        let cli = Cli {
+            output: vec![
+                parse_output_spec(&format!("json:{}", tmp_dir.path().join("report.json").display())).unwrap(),
+                parse_output_spec("text:-").unwrap(),
+            ],
+            ..Cli::default()
+        };
+        let list = list_recursive(tmp_dir.path(), &cli).unwrap();
+        assert_eq!(list, vec!["a.txt"]);
+    }
+
+    #[test]
+    fn prom_file_and_its_temporary_file_are_excluded() {
+        let tmp_dir = tempdir().unwrap();
+        File::create(tmp_dir.path().join("scrub.prom")).unwrap();
+        File::create(tmp_dir.path().join(".scrub.prom.tmp")).unwrap();
+        File::create(tmp_dir.path().join("a.txt")).unwrap();
+
+        let cli = Cli {
+            output: vec![parse_output_spec(&format!("prom:{}", tmp_dir.path().join("scrub.prom").display())).unwrap()],
+            ..Cli::default()
+        };
+        let list = list_recursive(tmp_dir.path(), &cli).unwrap();
+        assert_eq!(list, vec!["a.txt"]);
+    }
+
+    #[test]
+    fn can_read_deep_and_wide() {
+        let tmp_dir = tempdir().unwrap();
+        let mut dir = tmp_dir.path().to_owned();
+        for i in 0..200 {
+            dir = dir.join(format!("d{}", i % 10));
+            fs::create_dir(&dir).unwrap();
+        }
+        File::create(dir.join("deep.txt")).unwrap();
+        for i in 0..2000 {
+            fs::create_dir(tmp_dir.path().join(format!("w{}", i))).unwrap();
+            File::create(tmp_dir.path().join(format!("w{}/f.txt", i))).unwrap();
+        }
+
+        let root = tmp_dir.into_path();
+        let list = list_recursive(&root, &Cli::default()).unwrap();
+
+        assert_eq!(list.len(), 200 + 1 + 2000 * 2);
+        assert_eq!(list.iter().filter(|e| e.ends_with("deep.txt")).count(), 1);
+    }
+
+    #[test]
+    fn consumer_can_abort() {
+        let tmp_dir = tempdir().unwrap();
+        for i in 0..3000 {
+            File::create(tmp_dir.path().join(format!("foo{}.txt", i))).unwrap();
+        }
+
+        let mut count = 0;
+        let result = walk(tmp_dir.path(), &Cli::default(), |_| {
+            count += 1;
+            if count == 10 {
+                Err(IoError { cause: ErrorKind::Other.into(), message: "Stop.".to_owned(), path: None })
+            } else {
+                Ok(())
+            }
+        });
+
+        assert_eq!(result.err().unwrap().message, "Stop.");
+        assert_eq!(count, 10);
+    }
+
+    #[test]
+    fn can_treat_non_exitent() {
+        let root = Path::new("non_exitent");
+        let cli = Cli::default();
+        assert_eq!(list_recursive(root, &cli).err().unwrap().cause.kind(), ErrorKind::NotFound);
     }
 
     #[test]
@@ -138,13 +599,9 @@ mod tests {
         let file_path_buf = tmp_dir.path().join("foo0.txt");
         let file_path = file_path_buf.as_path();
         File::create(file_path).unwrap();
-        let cli = Cli {
-            control_file: "".to_owned(),
-            exclude: vec![],
-            target_dir: "".to_owned(),
-        };
+        let cli = Cli::default();
 
-        assert_eq!(list_recursive(file_path, &cli).is_err(), true);
+        assert!(list_recursive(file_path, &cli).is_err());
     }
 
     #[test]
@@ -160,16 +617,16 @@ mod tests {
 
         let root = tmp_dir.into_path();
         let cli = Cli {
-            control_file: "".to_owned(),
             exclude: vec!["0*".to_owned()],
-            target_dir: "".to_owned(),
+            ..Cli::default()
         };
 
         let mut list = list_recursive(&root, &cli).unwrap();
         list.sort();
         
-        assert_eq!(list.len(), 2);
-        assert_eq!(list[0], "bar/foo1.txt");
-        assert_eq!(list[1], "foo0.txt");
+        assert_eq!(list.len(), 3);
+        assert_eq!(list[0], "bar/");
+        assert_eq!(list[1], "bar/foo1.txt");
+        assert_eq!(list[2], "foo0.txt");
    }
 }
\ No newline at end of file
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Key under which two paths are regarded as the same name on a normalizing or case-insensitive file system.
+pub fn fold_key(s: &str) -> String {
+    s.nfc().collect::<String>().to_lowercase()
+}
+
+/// Removes pairs of removed/added paths that are equal under Unicode normalization or case folding
+/// from both lists and returns them as (removed, added).
+pub fn pair_equivalent<'a>(removed: &mut Vec<&'a str>, added: &mut Vec<&'a str>) -> Vec<(&'a str, &'a str)> {
+    let mut candidates: HashMap<String, Vec<usize>> = HashMap::new();
+    for (idx, a) in added.iter().enumerate() {
+        candidates.entry(fold_key(a)).or_default().push(idx);
+    }
+
+    let mut pairs: Vec<(&'a str, &'a str)> = vec![];
+    let mut paired_added: Vec<usize> = vec![];
+    removed.retain(|r| {
+        match candidates.get_mut(&fold_key(r)).and_then(|idxs| idxs.pop()) {
+            None => true,
+            Some(idx) => {
+                pairs.push((r, added[idx]));
+                paired_added.push(idx);
+                false
+            }
+        }
+    });
+
+    paired_added.sort_unstable();
+    for idx in paired_added.into_iter().rev() {
+        added.remove(idx);
+    }
+
+    pairs
+}
+
+/// Groups of paths that share a parent directory and whose names only differ by case or normalization.
+pub fn case_collisions<'a, I: Iterator<Item = &'a str>>(paths: I) -> Vec<Vec<&'a str>> {
+    let mut groups: HashMap<(&'a str, String), Vec<&'a str>> = HashMap::new();
+    for p in paths {
+        let trimmed = p.trim_end_matches('/');
+        let (parent, name) = match trimmed.rfind('/') {
+            Some(idx) => (&trimmed[..idx], &trimmed[idx + 1..]),
+            None => ("", trimmed),
+        };
+        groups.entry((parent, fold_key(name))).or_default().push(p);
+    }
+
+    let mut ret: Vec<Vec<&'a str>> = groups.into_values().filter(|g| 1 < g.len()).collect();
+    ret.sort();
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fold_key, pair_equivalent, case_collisions};
+
+    #[test]
+    fn fold_key_normalizes() {
+        assert_eq!(fold_key("cafe\u{301}"), fold_key("caf\u{e9}"));
+        assert_eq!(fold_key("ABC"), fold_key("abc"));
+        assert_ne!(fold_key("ABC"), fold_key("abd"));
+    }
+
+    #[test]
+    fn can_pair_equivalent() {
+        let mut removed = vec!["caf\u{e9}.txt", "gone.txt"];
+        let mut added = vec!["README", "cafe\u{301}.txt", "readme"];
+        let pairs = pair_equivalent(&mut removed, &mut added);
+
+        assert_eq!(pairs, vec![("caf\u{e9}.txt", "cafe\u{301}.txt")]);
+        assert_eq!(removed, vec!["gone.txt"]);
+        assert_eq!(added, vec!["README", "readme"]);
+    }
+
+    #[test]
+    fn can_find_case_collisions() {
+        let paths = vec!["a/", "a/Foo", "a/foo", "b/", "b/foo", "README", "readme/"];
+        assert_eq!(
+            case_collisions(paths.into_iter()),
+            vec![vec!["README", "readme/"], vec!["a/Foo", "a/foo"]]
+        );
+    }
+}